@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::{Certificate, Client, Proxy, RequestBuilder, StatusCode};
+use url::Url;
+
+use crate::common::*;
+use crate::syndication::parse_date;
+
+/// The client every request of a command goes through
+pub fn build_client(config: &Config) -> Result<Client, GoodMorningError> {
+    let user_agent = HeaderValue::from_str(&config.user_agent).map_err(|_| {
+        GoodMorningError::InvalidConfig(format!("invalid user agent {:?}", config.user_agent))
+    })?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, user_agent);
+
+    // feeds are decompressed by the syndication module, which also handles deflate and brotli
+    let mut builder = Client::builder()
+        .gzip(false)
+        .default_headers(headers)
+        .connect_timeout(Duration::from_secs(config.connect_timeout))
+        .timeout(Duration::from_secs(config.read_timeout));
+
+    if let Some(ref proxy) = config.proxy {
+        let proxy = Url::parse(proxy).map_err(|_| {
+            GoodMorningError::InvalidConfig(format!("invalid proxy url {:?}", proxy))
+        })?;
+        let no_proxy = config.no_proxy.clone();
+
+        builder = builder.proxy(Proxy::custom(move |url| {
+            if bypasses_proxy(url.host_str().unwrap_or(""), &no_proxy) {
+                None
+            } else {
+                Some(proxy.clone())
+            }
+        }));
+    }
+
+    if let Some(ref ca_bundle) = config.ca_bundle {
+        for certificate in read_certificates(ca_bundle)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// `no_proxy` entries match their host and its subdomains, `*` matches every host
+fn bypasses_proxy(host: &str, no_proxy: &[String]) -> bool {
+    no_proxy.iter().any(|entry| {
+        let entry = entry.trim().trim_start_matches('.');
+        entry == "*"
+            || host.eq_ignore_ascii_case(entry)
+            || host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+    })
+}
+
+// a bundle holds several certificates, each of them is read on its own
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, GoodMorningError> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let pem = fs::read_to_string(path)?;
+    let certificates = pem
+        .split_terminator(END)
+        .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|block| Certificate::from_pem(format!("{}{}\n", block.trim(), END).as_bytes()))
+        .collect::<Result<Vec<Certificate>, reqwest::Error>>()?;
+
+    if certificates.is_empty() {
+        return Err(GoodMorningError::InvalidConfig(format!(
+            "no certificate found in {:?}",
+            path
+        )));
+    }
+
+    Ok(certificates)
+}
+
+/// Add the credential and the headers of a subscription to its request
+pub fn authorize(
+    request: RequestBuilder,
+    access: &FeedAccess,
+    credentials: &HashMap<String, Credential>,
+) -> Result<RequestBuilder, GoodMorningError> {
+    let mut request = match access.credential {
+        None => request,
+        Some(ref name) => match credentials.get(name) {
+            None => {
+                return Err(GoodMorningError::InvalidConfig(format!(
+                    "unknown credential {:?}",
+                    name
+                )))
+            }
+            Some(Credential::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            Some(Credential::Bearer { token }) => request.bearer_auth(token),
+            Some(Credential::Header { name, value }) => {
+                request.header(name.as_str(), value.as_str())
+            }
+        },
+    };
+
+    for (name, value) in access.headers.iter() {
+        request = request.header(name.as_str(), value.as_str());
+    }
+
+    Ok(request)
+}
+
+/// Politeness towards the hosts, shared by the workers: one request at a time per host,
+/// at least `delay` between two of them, none while the host is paused and none once it asked
+/// us to back off.
+pub struct HostThrottle {
+    delay: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct HostState {
+    busy: bool,
+    last_request: Option<Instant>,
+    paused_until: Option<Instant>,
+    backed_off: bool,
+}
+
+/// Our turn on a host, given back when dropped
+pub struct HostPermit<'a> {
+    throttle: &'a HostThrottle,
+    host: String,
+}
+
+impl HostThrottle {
+    pub fn new(delay: Duration) -> HostThrottle {
+        HostThrottle {
+            delay,
+            hosts: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for our turn on `host`, fails when the host asked us to back off during this run.
+    pub fn acquire(&self, host: &str) -> Result<HostPermit<'_>, GoodMorningError> {
+        let mut hosts = self.hosts.lock().unwrap();
+
+        loop {
+            let state = hosts.entry(host.to_string()).or_default();
+
+            if state.backed_off {
+                return Err(GoodMorningError::HostBackOff(host.to_string()));
+            }
+
+            let now = Instant::now();
+            let wait = state
+                .last_request
+                .map(|last| self.delay.checked_sub(last.elapsed()).unwrap_or_default())
+                .unwrap_or_default()
+                .max(
+                    state
+                        .paused_until
+                        .map(|until| until.saturating_duration_since(now))
+                        .unwrap_or_default(),
+                );
+
+            if state.busy {
+                hosts = self.released.wait(hosts).unwrap();
+            } else if wait > Duration::from_secs(0) {
+                hosts = self.released.wait_timeout(hosts, wait).unwrap().0;
+            } else {
+                state.busy = true;
+                return Ok(HostPermit {
+                    throttle: self,
+                    host: host.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Leave `host` alone for `delay`, the requests to it wait until then
+    pub fn pause(&self, host: &str, delay: Duration) {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.entry(host.to_string()).or_default().paused_until = Some(Instant::now() + delay);
+        self.released.notify_all();
+    }
+
+    /// Leave `host` alone for the rest of the run
+    pub fn back_off(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.entry(host.to_string()).or_default().backed_off = true;
+        self.released.notify_all();
+    }
+}
+
+impl<'a> Drop for HostPermit<'a> {
+    fn drop(&mut self) {
+        let mut hosts = self.throttle.hosts.lock().unwrap();
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.busy = false;
+            state.last_request = Some(Instant::now());
+        }
+        self.throttle.released.notify_all();
+    }
+}
+
+/// How requests failing on a transient error are tried again
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &Config) -> RetryPolicy {
+        RetryPolicy {
+            retries: config.retries,
+            base_delay: Duration::from_millis(config.retry_delay),
+        }
+    }
+
+    /// Exponential delay before the retry following `attempt` (starting at 1),
+    /// randomly shortened by up to half so that workers don't retry in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay * 2u32.pow(attempt.saturating_sub(1).min(16));
+        let jitter = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos()
+            % 1000;
+
+        exponential / 2 + exponential / 2 * jitter / 1000
+    }
+}
+
+/// Timeouts, connection failures (dns ones included) and gateway errors may not happen again,
+/// unlike 404, 410, invalid requests or parse errors
+pub fn is_transient(error: &GoodMorningError) -> bool {
+    match error {
+        GoodMorningError::HttpError(error) => {
+            if let Some(status) = error.status() {
+                return status == StatusCode::BAD_GATEWAY
+                    || status == StatusCode::SERVICE_UNAVAILABLE
+                    || status == StatusCode::GATEWAY_TIMEOUT;
+            }
+
+            if error.is_timeout() {
+                return true;
+            }
+
+            // connection errors are io errors wrapped by hyper
+            let mut cause = error
+                .get_ref()
+                .map(|cause| cause as &(dyn StdError + 'static));
+            while let Some(error) = cause {
+                if let Some(io_error) = error.downcast_ref::<io::Error>() {
+                    return is_connection_error(io_error);
+                }
+                cause = error.source();
+            }
+
+            false
+        }
+        // failed reads of the body
+        GoodMorningError::IoError(error) => is_connection_error(error),
+        _ => false,
+    }
+}
+
+fn is_connection_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+    )
+        // dns failures have no kind of their own
+        || error
+            .to_string()
+            .starts_with("failed to lookup address information")
+}
+
+/// Read `input` whole, failing as soon as it's bigger than `max` bytes
+pub fn read_limited<R: Read>(input: R, max: u64) -> Result<Vec<u8>, GoodMorningError> {
+    let mut bytes = Vec::new();
+    input.take(max.saturating_add(1)).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > max {
+        return Err(GoodMorningError::BodyTooLarge(max));
+    }
+
+    Ok(bytes)
+}
+
+/// Status of the response a request failed on
+pub fn http_status(error: &GoodMorningError) -> Option<StatusCode> {
+    match error {
+        GoodMorningError::HttpError(error) => error.status(),
+        _ => None,
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an http date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    parse_date(value).map(|date| {
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+    })
+}
+
+#[test]
+pub fn should_limit_body_size() {
+    assert_eq!(read_limited(&b"<rss/>"[..], 6).unwrap(), b"<rss/>".to_vec());
+    match read_limited(&b"<rss></rss>"[..], 6) {
+        Err(GoodMorningError::BodyTooLarge(6)) => (),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+pub fn should_bypass_proxy_for_listed_hosts() {
+    let no_proxy = vec!["localhost".to_string(), ".corp.example.com".to_string()];
+
+    assert!(bypasses_proxy("localhost", &no_proxy));
+    assert!(bypasses_proxy("gitlab.corp.example.com", &no_proxy));
+    assert!(bypasses_proxy("corp.example.com", &no_proxy));
+    assert!(!bypasses_proxy("example.com", &no_proxy));
+    assert!(!bypasses_proxy("notcorp.example.com", &no_proxy));
+    assert!(bypasses_proxy("example.com", &["*".to_string()]));
+}
+
+#[test]
+pub fn should_authorize_requests() {
+    let mut credentials = HashMap::new();
+    credentials.insert(
+        "gitlab".to_string(),
+        Credential::Header {
+            name: "PRIVATE-TOKEN".to_string(),
+            value: "secret".to_string(),
+        },
+    );
+    credentials.insert(
+        "newsletter".to_string(),
+        Credential::Bearer {
+            token: "secret".to_string(),
+        },
+    );
+    let access = |credential: &str| FeedAccess {
+        credential: Some(credential.to_string()),
+        headers: vec![parse_header("Accept: application/atom+xml").unwrap()],
+    };
+    let client = Client::new();
+
+    let request = authorize(
+        client.get("http://example.org/feed"),
+        &access("gitlab"),
+        &credentials,
+    )
+    .unwrap()
+    .build()
+    .unwrap();
+    assert_eq!(request.headers()["PRIVATE-TOKEN"], "secret");
+    assert_eq!(request.headers()["Accept"], "application/atom+xml");
+
+    let request = authorize(
+        client.get("http://example.org/feed"),
+        &access("newsletter"),
+        &credentials,
+    )
+    .unwrap()
+    .build()
+    .unwrap();
+    assert_eq!(request.headers()["Authorization"], "Bearer secret");
+
+    assert!(authorize(
+        client.get("http://example.org/feed"),
+        &access("unknown"),
+        &credentials
+    )
+    .is_err());
+}
+
+#[test]
+pub fn should_back_off_exponentially() {
+    let policy = RetryPolicy {
+        retries: 3,
+        base_delay: Duration::from_millis(100),
+    };
+
+    for attempt in 1..4 {
+        let full = Duration::from_millis(100) * 2u32.pow(attempt - 1);
+        let delay = policy.delay(attempt);
+        assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+    }
+}
+
+#[test]
+pub fn should_not_retry_permanent_errors() {
+    assert!(!is_transient(&GoodMorningError::Parse));
+    assert!(!is_transient(&GoodMorningError::InvalidFeed(vec![])));
+    assert!(!is_transient(&GoodMorningError::HostBackOff(
+        "example.org".to_string()
+    )));
+
+    let client = Client::new();
+    let invalid_header = client
+        .get("http://example.org/feed")
+        .header("invalid header", "value")
+        .build()
+        .unwrap_err();
+    assert!(invalid_header.is_http());
+    assert!(!is_transient(&GoodMorningError::HttpError(invalid_header)));
+
+    // nothing listens on the port 1 of the loopback
+    let refused = client.get("http://127.0.0.1:1/feed").send().unwrap_err();
+    assert!(is_transient(&GoodMorningError::HttpError(refused)));
+}
+
+#[test]
+pub fn should_parse_retry_after() {
+    assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(Duration::from_secs(0))
+    );
+    assert!(parse_retry_after("Fri, 31 Dec 9999 23:59:59 GMT").unwrap() > Duration::from_secs(0));
+    assert_eq!(parse_retry_after("soon"), None);
+}
+
+#[test]
+pub fn should_space_requests_to_the_same_host() {
+    let throttle = HostThrottle::new(Duration::from_millis(100));
+    let start = Instant::now();
+
+    drop(throttle.acquire("example.org").unwrap());
+    drop(throttle.acquire("example.com").unwrap());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    drop(throttle.acquire("example.org").unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+pub fn should_defer_hosts_asking_to_back_off() {
+    let throttle = HostThrottle::new(Duration::from_millis(0));
+
+    let permit = throttle.acquire("example.org").unwrap();
+    throttle.back_off("example.org");
+    drop(permit);
+
+    assert!(throttle.acquire("example.org").is_err());
+    assert!(throttle.acquire("example.com").is_ok());
+}
+
+#[test]
+pub fn should_wait_for_paused_hosts() {
+    let throttle = HostThrottle::new(Duration::from_millis(0));
+    let start = Instant::now();
+
+    throttle.pause("example.org", Duration::from_millis(100));
+    drop(throttle.acquire("example.com").unwrap());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    drop(throttle.acquire("example.org").unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use super::enclosure::sanitize_file_name;
+use crate::common::*;
+use crate::syndication::{fetch_single_file_page, parse_date, CheckContext};
+
+/// Save the pages of the articles under `dir/<feed title>/<date>/<article title>.html`,
+/// as single html files. Articles whose page can't be saved are kept without their copy.
+pub fn archive_articles(
+    context: &CheckContext,
+    dir: &Path,
+    feed: &MonitoredFeed,
+    feed_title: &str,
+    articles: Vec<Article>,
+) -> Vec<Article> {
+    articles
+        .into_iter()
+        .map(|article| {
+            let link = match article.link {
+                Some(ref link) => link.clone(),
+                None => return article,
+            };
+
+            let path = archive_path(dir, feed, feed_title, &article);
+            match save_page(context, &link, &path) {
+                Ok(path) => {
+                    debug!("Archived {:?} to {:?}", link, path);
+                    Article {
+                        archive_path: Some(path),
+                        ..article
+                    }
+                }
+                Err(err) => {
+                    warn!("Could not archive {:?} ({:?})", link, err);
+                    article
+                }
+            }
+        })
+        .collect()
+}
+
+fn archive_path(dir: &Path, feed: &MonitoredFeed, feed_title: &str, article: &Article) -> PathBuf {
+    let date = article
+        .pub_date
+        .as_ref()
+        .and_then(|date| parse_date(date))
+        .unwrap_or_else(|| Utc::now().into());
+    let title = article
+        .title
+        .as_ref()
+        .or(article.link.as_ref())
+        .map_or("article", String::as_str);
+
+    dir.join(sanitize_file_name(feed_title, &feed.url))
+        .join(date.format("%Y-%m-%d").to_string())
+        .join(format!("{}.html", sanitize_file_name(title, "article")))
+}
+
+// articles of the same day with the same title don't overwrite each other
+fn save_page(context: &CheckContext, link: &str, path: &Path) -> Result<PathBuf, GoodMorningError> {
+    let page = fetch_single_file_page(context, link)?;
+
+    let mut path = path.to_path_buf();
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned());
+    let mut copy = 1;
+    while path.exists() {
+        copy += 1;
+        path.set_file_name(format!(
+            "{} ({}).html",
+            stem.as_deref().unwrap_or("article"),
+            copy
+        ));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, page)?;
+
+    Ok(fs::canonicalize(&path)?)
+}
+
+#[test]
+pub fn should_archive_by_feed_and_date() {
+    let feed = MonitoredFeed {
+        id: 1,
+        url: "https://example.org/feed.xml".to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let article = Article {
+        title: Some("Launch day: in pictures".to_string()),
+        link: Some("https://example.org/2019/launch".to_string()),
+        pub_date: Some("Tue, 8 Jan 2019 01:15:00 GMT".to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        archive_path(Path::new("/archive"), &feed, "Liftoff News", &article),
+        Path::new("/archive/Liftoff News/2019-01-08/Launch day_ in pictures.html")
+    );
+
+    let untitled = Article {
+        title: None,
+        ..article
+    };
+    assert_eq!(
+        archive_path(Path::new("/archive"), &feed, "", &untitled),
+        Path::new(
+            "/archive/https___example.org_feed.xml/2019-01-08/https___example.org_2019_launch.html"
+        )
+    );
+}
@@ -1,9 +1,11 @@
 use std::str::FromStr;
 
+use chrono::Utc;
 use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use rusqlite::{Connection, OpenFlags, Result, NO_PARAMS};
 
 use crate::common::*;
+use crate::syndication::parse_date;
 
 #[derive(Debug)]
 pub struct SQliteSubscriptionRepository {
@@ -21,6 +23,103 @@ impl SQliteSubscriptionRepository {
     fn execute(&self, sql: &str) -> Result<usize> {
         self.conn.execute(sql, NO_PARAMS)
     }
+
+    // tables created by older versions miss the columns added since
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns: Vec<String> = stmt
+            .query_map(NO_PARAMS, |row| row.get(1))?
+            .collect::<Result<_>>()?;
+
+        if !columns.iter().any(|existing| existing == column) {
+            debug!("Adding column {}.{}", table, column);
+            self.execute(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    // feeds parsed leniently may have no last article, older tables required one
+    fn allow_checks_without_article(&self) -> Result<()> {
+        let required: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('subscription_check')
+            WHERE name = 'last_article_title' AND \"notnull\" = 1",
+            NO_PARAMS,
+            |row| row.get(0),
+        )?;
+        if !required {
+            return Ok(());
+        }
+
+        debug!("Allowing checks without last article");
+        self.conn.execute_batch(
+            "BEGIN;
+            CREATE TABLE subscription_check_new (
+                id                    INTEGER PRIMARY KEY,
+                subscription_id       INTEGER NOT NULL,
+
+                check_date            TEXT NOT NULL,
+                title                 TEXT NOT NULL,
+                pub_date              TEXT,
+
+                last_article_title    TEXT,
+                last_article_guid     TEXT,
+                last_article_pub_date TEXT,
+                last_article_hash     TEXT,
+
+
+                FOREIGN KEY(subscription_id) REFERENCES subscription(id)
+            );
+            INSERT INTO subscription_check_new (id, subscription_id, check_date, title, pub_date, last_article_title, last_article_guid, last_article_pub_date, last_article_hash)
+                SELECT id, subscription_id, check_date, title, pub_date, last_article_title, last_article_guid, last_article_pub_date, last_article_hash
+                FROM subscription_check;
+            DROP TABLE subscription_check;
+            ALTER TABLE subscription_check_new RENAME TO subscription_check;
+            COMMIT;",
+        )
+    }
+
+    // full-text index of the articles, kept up to date by triggers
+    fn init_article_search(&self) -> Result<()> {
+        let exists: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'article_search'",
+            NO_PARAMS,
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Ok(());
+        }
+
+        debug!("Creating the article search index");
+        self.conn.execute_batch(
+            "BEGIN;
+            CREATE VIRTUAL TABLE article_search USING fts5(
+                title, summary, content, author,
+                content='article', content_rowid='id'
+            );
+            CREATE TRIGGER article_search_insert AFTER INSERT ON article BEGIN
+                INSERT INTO article_search (rowid, title, summary, content, author)
+                VALUES (new.id, new.title, new.summary, new.content, new.author);
+            END;
+            CREATE TRIGGER article_search_delete AFTER DELETE ON article BEGIN
+                INSERT INTO article_search (article_search, rowid, title, summary, content, author)
+                VALUES ('delete', old.id, old.title, old.summary, old.content, old.author);
+            END;
+            CREATE TRIGGER article_search_update AFTER UPDATE ON article BEGIN
+                INSERT INTO article_search (article_search, rowid, title, summary, content, author)
+                VALUES ('delete', old.id, old.title, old.summary, old.content, old.author);
+                INSERT INTO article_search (rowid, title, summary, content, author)
+                VALUES (new.id, new.title, new.summary, new.content, new.author);
+            END;
+            INSERT INTO article_search (article_search) VALUES ('rebuild');
+            COMMIT;",
+        )
+    }
 }
 
 impl FromSql for FeedType {
@@ -50,6 +149,23 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
         )
         .unwrap();
 
+        self.add_column_if_missing("subscription", "retired_date", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("subscription", "retired_reason", "TEXT")
+            .unwrap();
+        self.add_column_if_missing(
+            "subscription",
+            "not_found_streak",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .unwrap();
+        self.add_column_if_missing("subscription", "credential", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("subscription", "headers", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("subscription", "full_text", "INTEGER NOT NULL DEFAULT 0")
+            .unwrap();
+
         self.execute(
             "CREATE TABLE IF NOT EXISTS subscription_check (
                 id                    INTEGER PRIMARY KEY,
@@ -59,16 +175,66 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
                 title                 TEXT NOT NULL,
                 pub_date              TEXT,
 
-                last_article_title    TEXT NOT NULL,
-                last_article_guid     TEXT NOT NULL,
-                last_article_pub_date TEXT NOT NULL,
-                last_article_hash     TEXT NOT NULL,
+                last_article_title    TEXT,
+                last_article_guid     TEXT,
+                last_article_pub_date TEXT,
+                last_article_hash     TEXT,
 
 
                 FOREIGN KEY(subscription_id) REFERENCES subscription(id)
             )",
         )
         .unwrap();
+
+        self.allow_checks_without_article().unwrap();
+
+        self.execute(
+            "CREATE TABLE IF NOT EXISTS subscription_failure (
+                id                    INTEGER PRIMARY KEY,
+                subscription_id       INTEGER NOT NULL,
+
+                check_date            TEXT NOT NULL,
+                error                 TEXT NOT NULL,
+
+                FOREIGN KEY(subscription_id) REFERENCES subscription(id)
+            )",
+        )
+        .unwrap();
+
+        self.execute(
+            "CREATE TABLE IF NOT EXISTS article (
+                id                    INTEGER PRIMARY KEY,
+                subscription_id       INTEGER NOT NULL,
+
+                guid                  TEXT,
+                title                 TEXT,
+                link                  TEXT,
+                pub_date              TEXT,
+                summary               TEXT,
+                content               TEXT,
+                author                TEXT,
+                archive_path          TEXT,
+
+                url                   TEXT,
+                content_hash          TEXT,
+                notified_date         TEXT NOT NULL,
+                published_date        TEXT,
+
+                FOREIGN KEY(subscription_id) REFERENCES subscription(id)
+            )",
+        )
+        .unwrap();
+
+        self.add_column_if_missing("article", "content", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("article", "archive_path", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("article", "author", "TEXT")
+            .unwrap();
+        self.add_column_if_missing("article", "published_date", "TEXT")
+            .unwrap();
+
+        self.init_article_search().unwrap();
     }
 
     fn get_monitored_feeds(&self) -> Vec<MonitoredFeed> {
@@ -90,6 +256,11 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
                         sc.last_article_guid,
                         sc.last_article_pub_date,
                         sc.last_article_hash,
+                        s.retired_reason,
+                        s.not_found_streak,
+                        s.credential,
+                        s.headers,
+                        s.full_text,
                         ROW_NUMBER() OVER (PARTITION BY s.id ORDER BY check_date DESC) AS rownumber
                     FROM subscription AS s
                         LEFT OUTER JOIN  subscription_check AS sc ON sc.subscription_id = s.id
@@ -100,6 +271,7 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
 
         stmt.query_map(NO_PARAMS, |row| {
             let check_id: Option<u32> = row.get(3);
+            let headers: Option<String> = row.get(14);
 
             let last_check = check_id.map(|_check_id| FeedCheckResult {
                 check_date: row.get(4),
@@ -109,6 +281,7 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
                 last_article_guid: row.get(8),
                 last_article_pub_date: row.get(9),
                 last_article_hash: row.get(10),
+                articles: Vec::new(),
             });
 
             MonitoredFeed {
@@ -116,6 +289,15 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
                 url: row.get(1),
                 kind: row.get(2),
                 last_check,
+                retired: row.get(11),
+                not_found_streak: row.get(12),
+                access: FeedAccess {
+                    credential: row.get(13),
+                    headers: headers.as_ref().map_or(Vec::new(), |headers| {
+                        headers.lines().filter_map(parse_header).collect()
+                    }),
+                },
+                full_text: row.get(15),
             }
         })
         .unwrap()
@@ -152,4 +334,301 @@ impl SubscriptionRepository for SQliteSubscriptionRepository {
             )
             .unwrap();
     }
+
+    fn get_checks(&self, feed: &MonitoredFeed) -> Vec<FeedCheckResult> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT check_date, title, pub_date, last_article_title, last_article_guid, last_article_pub_date, last_article_hash
+                FROM subscription_check
+                WHERE subscription_id = ?1
+                ORDER BY check_date, id",
+            )
+            .unwrap();
+
+        stmt.query_map(&[&feed.id as &dyn ToSql], |row| FeedCheckResult {
+            check_date: row.get(0),
+            title: row.get(1),
+            pub_date: row.get(2),
+            last_article_title: row.get(3),
+            last_article_guid: row.get(4),
+            last_article_pub_date: row.get(5),
+            last_article_hash: row.get(6),
+            articles: Vec::new(),
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect()
+    }
+
+    fn add_failure(&self, feed: &MonitoredFeed, failure: &CheckFailure) {
+        debug!("Adding failure for feed {:?}", feed.id);
+        self.conn
+            .execute(
+                "INSERT INTO subscription_failure (subscription_id, check_date, error) VALUES (?1, ?2, ?3)",
+                &[
+                    &feed.id as &dyn ToSql,
+                    &failure.check_date as &dyn ToSql,
+                    &failure.error as &dyn ToSql,
+                ],
+            )
+            .unwrap();
+    }
+
+    fn get_failures(&self, feed: &MonitoredFeed) -> Vec<CheckFailure> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT check_date, error FROM subscription_failure WHERE subscription_id = ?1 ORDER BY check_date, id",
+            )
+            .unwrap();
+
+        stmt.query_map(&[&feed.id as &dyn ToSql], |row| CheckFailure {
+            check_date: row.get(0),
+            error: row.get(1),
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect()
+    }
+
+    fn retire_sub(&self, feed: &MonitoredFeed, reason: &str) {
+        debug!("Retiring feed {:?} ({})", feed.url, reason);
+        self.conn
+            .execute(
+                "UPDATE subscription SET retired_date = ?1, retired_reason = ?2 WHERE id = ?3",
+                &[
+                    &Utc::now().to_rfc3339() as &dyn ToSql,
+                    &reason as &dyn ToSql,
+                    &feed.id as &dyn ToSql,
+                ],
+            )
+            .unwrap();
+    }
+
+    fn set_access(&self, feed: &MonitoredFeed, access: &FeedAccess) {
+        debug!("Setting access of feed {:?}", feed.url);
+        let headers = Some(access.headers_text()).filter(|headers| !headers.is_empty());
+        self.conn
+            .execute(
+                "UPDATE subscription SET credential = ?1, headers = ?2 WHERE id = ?3",
+                &[
+                    &access.credential as &dyn ToSql,
+                    &headers as &dyn ToSql,
+                    &feed.id as &dyn ToSql,
+                ],
+            )
+            .unwrap();
+    }
+
+    fn add_article(&self, feed: &MonitoredFeed, article: &Article, key: &ArticleKey) {
+        debug!("Adding article {:?}", article.link);
+        let archive_path = article
+            .archive_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned());
+        // `pub_date` as written in the feed, `published_date` comparable with the other dates
+        let published_date = article
+            .pub_date
+            .as_deref()
+            .and_then(parse_date)
+            .map(|date| date.with_timezone(&Utc).to_rfc3339());
+        self.conn
+            .execute(
+                "INSERT INTO article (subscription_id, guid, title, link, pub_date, summary, content, author, archive_path, url, content_hash, notified_date, published_date)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                &[
+                    &feed.id as &dyn ToSql,
+                    &article.guid as &dyn ToSql,
+                    &article.title as &dyn ToSql,
+                    &article.link as &dyn ToSql,
+                    &article.pub_date as &dyn ToSql,
+                    &article.summary as &dyn ToSql,
+                    &article.content as &dyn ToSql,
+                    &article.author as &dyn ToSql,
+                    &archive_path as &dyn ToSql,
+                    &key.url as &dyn ToSql,
+                    &key.content_hash as &dyn ToSql,
+                    &Utc::now().to_rfc3339() as &dyn ToSql,
+                    &published_date as &dyn ToSql,
+                ],
+            )
+            .unwrap();
+    }
+
+    fn get_article_keys(&self, since: &str) -> Vec<ArticleKey> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT url, content_hash FROM article WHERE notified_date >= ?1")
+            .unwrap();
+
+        stmt.query_map(&[&since as &dyn ToSql], |row| ArticleKey {
+            url: row.get(0),
+            content_hash: row.get(1),
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect()
+    }
+
+    fn search_articles(&self, query: &ArticleQuery) -> Vec<SearchResult> {
+        let mut sql = "
+            SELECT s.url,
+                a.title,
+                a.link,
+                COALESCE(a.published_date, a.notified_date) AS date,
+                snippet(article_search, -1, '[', ']', '…', 16)
+            FROM article_search
+                JOIN article AS a ON a.id = article_search.rowid
+                JOIN subscription AS s ON s.id = a.subscription_id
+            WHERE article_search MATCH ?1"
+            .to_string();
+        let mut params: Vec<&dyn ToSql> = vec![&query.text];
+
+        let feed_patterns: Vec<String> = query
+            .feeds
+            .iter()
+            .map(|feed| format!("%{}%", feed))
+            .collect();
+        if !feed_patterns.is_empty() {
+            let mut conditions = Vec::new();
+            for pattern in feed_patterns.iter() {
+                params.push(pattern);
+                conditions.push(format!("s.url LIKE ?{}", params.len()));
+            }
+            sql.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+        }
+        if let Some(ref since) = query.since {
+            params.push(since);
+            sql.push_str(&format!(" AND date >= ?{}", params.len()));
+        }
+        if let Some(ref until) = query.until {
+            params.push(until);
+            sql.push_str(&format!(" AND date < ?{}", params.len()));
+        }
+
+        // matches in the title count the most, then the summary, the author and the content
+        params.push(&query.limit);
+        sql.push_str(&format!(
+            " ORDER BY bm25(article_search, 10.0, 5.0, 1.0, 2.0) LIMIT ?{}",
+            params.len()
+        ));
+
+        let mut stmt = self.conn.prepare(&sql).unwrap();
+        stmt.query_map(&params, |row| SearchResult {
+            feed_url: row.get(0),
+            title: row.get(1),
+            link: row.get(2),
+            date: row.get(3),
+            snippet: row.get(4),
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect()
+    }
+
+    fn set_url(&self, feed: &MonitoredFeed, url: &str) {
+        debug!("Renaming feed {:?} to {:?}", feed.url, url);
+        self.conn
+            .execute(
+                "UPDATE subscription SET url = ?1 WHERE id = ?2",
+                &[&url as &dyn ToSql, &feed.id as &dyn ToSql],
+            )
+            .unwrap();
+    }
+
+    fn set_full_text(&self, feed: &MonitoredFeed, full_text: bool) {
+        debug!(
+            "Setting full text of feed {:?} to {:?}",
+            feed.url, full_text
+        );
+        self.conn
+            .execute(
+                "UPDATE subscription SET full_text = ?1 WHERE id = ?2",
+                &[&full_text as &dyn ToSql, &feed.id as &dyn ToSql],
+            )
+            .unwrap();
+    }
+
+    fn merge_sub(&self, into: &MonitoredFeed, from: &MonitoredFeed) {
+        debug!("Merging feed {:?} into {:?}", from.url, into.url);
+        self.conn
+            .execute_batch(&format!(
+                "BEGIN;
+                UPDATE subscription_check SET subscription_id = {into} WHERE subscription_id = {from};
+                UPDATE subscription_failure SET subscription_id = {into} WHERE subscription_id = {from};
+                UPDATE article SET subscription_id = {into} WHERE subscription_id = {from};
+                DELETE FROM subscription WHERE id = {from};
+                COMMIT;",
+                into = into.id,
+                from = from.id
+            ))
+            .unwrap();
+    }
+
+    fn set_not_found_streak(&self, feed: &MonitoredFeed, streak: u32) {
+        self.conn
+            .execute(
+                "UPDATE subscription SET not_found_streak = ?1 WHERE id = ?2",
+                &[&streak as &dyn ToSql, &feed.id as &dyn ToSql],
+            )
+            .unwrap();
+    }
+}
+
+#[test]
+pub fn should_store_checks_of_empty_channels() {
+    let repo = SQliteSubscriptionRepository::new(":memory:");
+    // tables created by an older version
+    repo.execute(
+        "CREATE TABLE subscription (
+            id          INTEGER PRIMARY KEY,
+            url         TEXT NOT NULL UNIQUE,
+            kind        TEXT NOT NULL
+        )",
+    )
+    .unwrap();
+    repo.execute("INSERT INTO subscription VALUES (1, 'https://example.org/feed', 'rss')")
+        .unwrap();
+    repo.execute(
+        "CREATE TABLE subscription_check (
+            id                    INTEGER PRIMARY KEY,
+            subscription_id       INTEGER NOT NULL,
+            check_date            TEXT NOT NULL,
+            title                 TEXT NOT NULL,
+            pub_date              TEXT,
+            last_article_title    TEXT NOT NULL,
+            last_article_guid     TEXT NOT NULL,
+            last_article_pub_date TEXT NOT NULL,
+            last_article_hash     TEXT NOT NULL
+        )",
+    )
+    .unwrap();
+    repo.execute(
+        "INSERT INTO subscription_check VALUES (1, 1, '2019-01-01T00:00:00+00:00', 'Old', NULL, 'A', 'a', '2019-01-01', 'None')",
+    )
+    .unwrap();
+
+    repo.init();
+    let feed = repo.get_monitored_feeds().remove(0);
+
+    repo.add_check(
+        &feed,
+        &FeedCheckResult {
+            check_date: "2019-01-02T00:00:00+00:00".to_string(),
+            title: "Fresh feed".to_string(),
+            pub_date: None,
+            last_article_title: None,
+            last_article_guid: None,
+            last_article_pub_date: None,
+            last_article_hash: None,
+            articles: Vec::new(),
+        },
+    );
+
+    let checks = repo.get_checks(&feed);
+    assert_eq!(checks.len(), 2);
+    assert_eq!(checks[0].last_article_title, Some("A".to_string()));
+    assert_eq!(checks[1].title, "Fresh feed");
+    assert_eq!(checks[1].last_article_guid, None);
 }
@@ -0,0 +1,205 @@
+use crate::common::*;
+use crate::syndication::url_key;
+
+/// Identify an article by its normalized link, and by a hash of its title and summary.
+pub fn article_key(article: &Article) -> ArticleKey {
+    ArticleKey {
+        url: article.link.as_deref().map(url_key),
+        content_hash: content_hash(article),
+    }
+}
+
+impl ArticleKey {
+    pub fn same_article(&self, other: &ArticleKey) -> bool {
+        let same = |a: &Option<String>, b: &Option<String>| a.is_some() && a == b;
+        same(&self.url, &other.url) || same(&self.content_hash, &other.content_hash)
+    }
+}
+
+// case and spacing changes between feeds don't matter, a title alone is too common to tell
+// articles apart (`Weekly links`)
+fn content_hash(article: &Article) -> Option<String> {
+    let title = article.title.as_ref()?;
+    let summary = article
+        .summary
+        .as_ref()
+        .filter(|summary| !summary.trim().is_empty())?;
+    let text = format!("{}\n{}", title, summary);
+    let normalized = text
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+
+    Some(format!("{:016x}", fnv1a(normalized.as_bytes())))
+}
+
+// stable across runs and rust versions, unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Drop the articles notified recently (`notified`) and the ones already listed by a previous
+/// update of the run, the feeds of the dropped duplicates are noted in `also_in`.
+/// New article updates left without articles are dropped too.
+pub fn deduplicate(updates: &mut Vec<FeedUpdate>, notified: &[ArticleKey]) {
+    // (update index, article index, key) of the articles kept so far
+    let mut kept: Vec<(usize, usize, ArticleKey)> = Vec::new();
+    let mut emptied = Vec::with_capacity(updates.len());
+
+    for index in 0..updates.len() {
+        let articles = std::mem::take(&mut updates[index].new_articles);
+        let had_articles = !articles.is_empty();
+        let feed_url = updates[index].feed.url.clone();
+
+        for article in articles.into_iter() {
+            let key = article_key(&article);
+
+            if notified.iter().any(|known| known.same_article(&key)) {
+                debug!("Already notified {:?}", article.link);
+                continue;
+            }
+
+            let duplicate = kept
+                .iter()
+                .find(|(_, _, known)| known.same_article(&key))
+                .map(|(update, article, _)| (*update, *article));
+            match duplicate {
+                Some((update, first)) => {
+                    let also_in = &mut updates[update].new_articles[first].also_in;
+                    if update != index && !also_in.contains(&feed_url) {
+                        also_in.push(feed_url.clone());
+                    }
+                }
+                None => {
+                    kept.push((index, updates[index].new_articles.len(), key));
+                    updates[index].new_articles.push(article);
+                }
+            }
+        }
+
+        // title changes and retirements are told even without articles
+        emptied.push(
+            had_articles
+                && updates[index].new_articles.is_empty()
+                && matches!(
+                    updates[index].kind,
+                    FeedUpdateKind::NewArticle | FeedUpdateKind::LastArticle
+                ),
+        );
+    }
+
+    let mut emptied = emptied.into_iter();
+    updates.retain(|_| !emptied.next().unwrap_or(false));
+}
+
+#[test]
+pub fn should_deduplicate_articles_across_feeds() {
+    let update = |url: &str, articles: Vec<Article>| FeedUpdate {
+        feed: MonitoredFeed {
+            id: 1,
+            url: url.to_string(),
+            kind: FeedType::Rss,
+            last_check: None,
+            retired: None,
+            not_found_streak: 0,
+            access: FeedAccess::default(),
+            full_text: false,
+        },
+        kind: FeedUpdateKind::NewArticle,
+        new_articles: articles,
+        suppressed: Vec::new(),
+    };
+    let article = |title: &str, link: &str| Article {
+        title: Some(title.to_string()),
+        link: Some(link.to_string()),
+        ..Default::default()
+    };
+    let summarized = |title: &str, link: &str, summary: &str| Article {
+        summary: Some(summary.to_string()),
+        ..article(title, link)
+    };
+
+    let mut updates = vec![
+        update(
+            "https://blog.example.org/feed",
+            vec![summarized(
+                "Why Rust",
+                "https://blog.example.org/why-rust/",
+                "Safety without a garbage collector",
+            )],
+        ),
+        update(
+            "https://aggregator.example.com/rss",
+            vec![
+                article(
+                    "Why Rust",
+                    "http://Blog.example.org/why-rust?utm_source=aggregator",
+                ),
+                summarized(
+                    "Why  rust",
+                    "https://mirror.example.net/why-rust",
+                    "Safety without a\n garbage collector",
+                ),
+                article("Already seen", "https://example.com/seen"),
+                article("Something else", "https://example.com/else"),
+            ],
+        ),
+    ];
+    updates.push(update(
+        "https://news.example.net/rss",
+        vec![article("Already seen", "http://example.com/seen/")],
+    ));
+    let notified = vec![article_key(&article(
+        "Already seen",
+        "https://example.com/seen",
+    ))];
+
+    deduplicate(&mut updates, &notified);
+
+    // nothing left to tell for the last feed
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].new_articles.len(), 1);
+    assert_eq!(
+        updates[0].new_articles[0].also_in,
+        vec!["https://aggregator.example.com/rss".to_string()]
+    );
+    let titles: Vec<&str> = updates[1]
+        .new_articles
+        .iter()
+        .filter_map(|article| article.title.as_deref())
+        .collect();
+    assert_eq!(titles, vec!["Something else"]);
+}
+
+#[test]
+pub fn should_keep_articles_with_only_the_same_title() {
+    let article = |link: &str| Article {
+        title: Some("Weekly links".to_string()),
+        link: Some(link.to_string()),
+        ..Default::default()
+    };
+    let mut updates = vec![FeedUpdate {
+        feed: MonitoredFeed {
+            id: 1,
+            url: "https://blog.example.org/feed".to_string(),
+            kind: FeedType::Rss,
+            last_check: None,
+            retired: None,
+            not_found_streak: 0,
+            access: FeedAccess::default(),
+            full_text: false,
+        },
+        kind: FeedUpdateKind::NewArticle,
+        new_articles: vec![article("https://blog.example.org/links/12")],
+        suppressed: Vec::new(),
+    }];
+    let notified = vec![article_key(&article("https://blog.example.org/links/11"))];
+
+    deduplicate(&mut updates, &notified);
+
+    assert_eq!(updates[0].new_articles.len(), 1);
+    assert_eq!(article_key(&updates[0].new_articles[0]).content_hash, None);
+}
@@ -0,0 +1,230 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::common::*;
+use crate::syndication::{check_feed, host_of, parse_date, CheckContext};
+
+/// Save the enclosures of the `max_per_feed` most recent articles having some, in every feed as
+/// it is now, to `dir/<feed title>/`. Whether they were notified doesn't matter: files already
+/// downloaded are skipped, partial downloads are resumed. Enclosures bigger than
+/// `max_enclosure_size` are not downloaded.
+pub fn download_enclosures(
+    repo: &dyn SubscriptionRepository,
+    dir: &Path,
+    max_per_feed: usize,
+    config: &Config,
+) -> Result<(), GoodMorningError> {
+    info!("Downloading enclosures to {:?}", dir);
+
+    let context = CheckContext::new(config)?;
+
+    for feed in repo.get_monitored_feeds().into_iter() {
+        if feed.retired.is_some() {
+            continue;
+        }
+
+        let check_result = match check_feed(&context, &feed) {
+            Ok(check_result) => check_result,
+            Err(_) => continue,
+        };
+
+        let feed_dir = dir.join(sanitize_file_name(&check_result.title, &feed.url));
+
+        let articles = check_result
+            .articles
+            .iter()
+            .filter(|article| !article.enclosures.is_empty())
+            .take(max_per_feed);
+
+        for article in articles {
+            for enclosure in article.enclosures.iter() {
+                let path = feed_dir.join(enclosure_file_name(article, enclosure));
+
+                if path.exists() {
+                    debug!("{:?} already downloaded", path);
+                    continue;
+                }
+
+                fs::create_dir_all(&feed_dir)?;
+
+                match download(&context, &enclosure.url, &path, config.max_enclosure_size) {
+                    Ok(()) => println!("Downloaded {}", path.display()),
+                    Err(err) => warn!(
+                        "Error happened while downloading {:?} ({:?})",
+                        enclosure.url, err
+                    ),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Download to a `.part` file first, so an interrupted download can be resumed
+fn download(
+    context: &CheckContext,
+    url: &str,
+    path: &Path,
+    max_size: u64,
+) -> Result<(), GoodMorningError> {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+
+    let offset = fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+
+    let _permit = context.hosts.acquire(&host_of(url))?;
+    let mut request = context.client.get(url);
+    if offset > 0 {
+        debug!("Resuming {:?} at byte {}", url, offset);
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+
+    let response = request.send()?;
+
+    // the partial file is already complete
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        fs::rename(&part_path, path)?;
+        return Ok(());
+    }
+
+    let mut response = response.error_for_status()?;
+
+    let (mut file, offset) = if response.status() == StatusCode::PARTIAL_CONTENT {
+        (OpenOptions::new().append(true).open(&part_path)?, offset)
+    } else {
+        // the server doesn't support ranges, start over
+        (File::create(&part_path)?, 0)
+    };
+
+    let remaining = max_size.saturating_sub(offset);
+    if response
+        .content_length()
+        .is_some_and(|length| length > remaining)
+    {
+        fs::remove_file(&part_path)?;
+        return Err(GoodMorningError::BodyTooLarge(max_size));
+    }
+
+    // the announced length may be missing or wrong
+    let copied = io::copy(&mut (&mut response).take(remaining + 1), &mut file)?;
+    if copied > remaining {
+        fs::remove_file(&part_path)?;
+        return Err(GoodMorningError::BodyTooLarge(max_size));
+    }
+
+    fs::rename(&part_path, path)?;
+
+    Ok(())
+}
+
+fn enclosure_file_name(article: &Article, enclosure: &Enclosure) -> String {
+    let name = Url::parse(&enclosure.url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|segments| segments.rev().find(|s| !s.is_empty()))
+                .map(|segment| segment.to_string())
+        })
+        .unwrap_or_else(|| "enclosure".to_string());
+
+    // the same file name is often used for every episode
+    match article.pub_date.as_ref().and_then(|date| parse_date(date)) {
+        Some(date) => sanitize_file_name(&format!("{} {}", date.format("%Y-%m-%d"), name), ""),
+        None => sanitize_file_name(&name, "enclosure"),
+    }
+}
+
+pub fn sanitize_file_name(name: &str, fallback: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || " -_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(100)
+        .collect();
+
+    let name = name.trim().trim_start_matches('.');
+
+    if name.is_empty() {
+        sanitize_file_name(fallback, "feed")
+    } else {
+        name.to_string()
+    }
+}
+
+#[test]
+pub fn should_name_enclosure_files_after_date_and_url() {
+    let article = Article {
+        pub_date: Some("Tue, 8 Jan 2019 01:15:00 GMT".to_string()),
+        ..Default::default()
+    };
+    let enclosure = Enclosure {
+        url: "https://example.com/podcasts/everest/Episode%201.mp3?from=rss".to_string(),
+        mime_type: None,
+        length: None,
+        duration: None,
+    };
+
+    assert_eq!(
+        enclosure_file_name(&article, &enclosure),
+        "2019-01-08 Episode_201.mp3"
+    );
+    assert_eq!(
+        enclosure_file_name(&Article::default(), &enclosure),
+        "Episode_201.mp3"
+    );
+    assert_eq!(
+        sanitize_file_name("../../etc/passwd", "x"),
+        "_.._etc_passwd"
+    );
+    assert_eq!(
+        sanitize_file_name("", "http://x.org/feed"),
+        "http___x.org_feed"
+    );
+}
+
+#[test]
+pub fn should_not_download_enclosures_bigger_than_the_limit() {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/episode.mp3", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 64\r\n\r\n");
+        let _ = stream.write_all(&[0; 64]);
+    });
+
+    let config: Config = toml::from_str(
+        r#"
+        sendgrid_token = ""
+        mail_to = ""
+        report_type = "Stdout"
+        "#,
+    )
+    .unwrap();
+    let context = CheckContext::new(&config).unwrap();
+    let path = std::env::temp_dir().join(format!("good-morning-{}.mp3", std::process::id()));
+
+    match download(&context, &url, &path, 16) {
+        Err(GoodMorningError::BodyTooLarge(16)) => (),
+        other => panic!("downloaded: {:?}", other),
+    }
+    assert!(!path.exists());
+    assert!(!path.with_extension("mp3.part").exists());
+}
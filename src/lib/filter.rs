@@ -0,0 +1,269 @@
+use regex::Regex;
+
+use crate::common::*;
+use crate::syndication::url_key;
+
+/// The filters of the config, ready to be applied
+pub struct Filters {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    action: FilterAction,
+    /// `url_key` of the feed the rule is restricted to
+    feed: Option<String>,
+    keywords: Vec<String>,
+    regex: Option<Regex>,
+    author: Option<String>,
+    category: Option<String>,
+    label: String,
+}
+
+impl Filters {
+    pub fn new(rules: &[FilterRule]) -> Result<Filters, GoodMorningError> {
+        let rules = rules.iter().map(Rule::new).collect::<Result<_, _>>()?;
+        Ok(Filters { rules })
+    }
+
+    /// Keep the articles of `feed` passing its rules and the global ones.
+    /// Articles are dropped when they match an exclude rule, or when the feed has include
+    /// rules and they match none of them. Returns how many articles each rule dropped.
+    pub fn apply(
+        &self,
+        feed: &MonitoredFeed,
+        articles: Vec<Article>,
+    ) -> (Vec<Article>, Vec<(String, usize)>) {
+        let feed_key = url_key(&feed.url);
+        let rules: Vec<&Rule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.feed.as_ref().is_none_or(|key| *key == feed_key))
+            .collect();
+        let includes: Vec<&Rule> = rules
+            .iter()
+            .cloned()
+            .filter(|rule| rule.action == FilterAction::Include)
+            .collect();
+        let include_label = format!(
+            "not matching {}",
+            includes
+                .iter()
+                .map(|rule| rule.label.as_str())
+                .collect::<Vec<&str>>()
+                .join(" or ")
+        );
+
+        let mut kept = Vec::new();
+        let mut suppressed: Vec<(String, usize)> = Vec::new();
+
+        for article in articles.into_iter() {
+            let excluded_by = rules
+                .iter()
+                .find(|rule| rule.action == FilterAction::Exclude && rule.matches(&article))
+                .map(|rule| &rule.label);
+            let not_included =
+                !includes.is_empty() && !includes.iter().any(|rule| rule.matches(&article));
+
+            let label = match excluded_by {
+                Some(label) => label,
+                None if not_included => &include_label,
+                None => {
+                    kept.push(article);
+                    continue;
+                }
+            };
+
+            match suppressed.iter_mut().find(|(rule, _)| rule == label) {
+                Some((_, count)) => *count += 1,
+                None => suppressed.push((label.clone(), 1)),
+            }
+        }
+
+        (kept, suppressed)
+    }
+}
+
+impl Rule {
+    fn new(rule: &FilterRule) -> Result<Rule, GoodMorningError> {
+        let regex = match rule.regex {
+            Some(ref regex) => Some(Regex::new(regex).map_err(|err| {
+                GoodMorningError::InvalidConfig(format!(
+                    "invalid filter regex {:?} ({})",
+                    regex, err
+                ))
+            })?),
+            None => None,
+        };
+
+        let mut criteria = Vec::new();
+        if !rule.keywords.is_empty() {
+            criteria.push(format!("keywords {}", rule.keywords.join(", ")));
+        }
+        if let Some(ref regex) = rule.regex {
+            criteria.push(format!("regex {}", regex));
+        }
+        if let Some(ref author) = rule.author {
+            criteria.push(format!("author {}", author));
+        }
+        if let Some(ref category) = rule.category {
+            criteria.push(format!("category {}", category));
+        }
+
+        if criteria.is_empty() {
+            return Err(GoodMorningError::InvalidConfig(
+                "filter without any criteria".to_string(),
+            ));
+        }
+
+        let label = match rule.name {
+            Some(ref name) => name.clone(),
+            None => {
+                let action = match rule.action {
+                    FilterAction::Include => "include",
+                    FilterAction::Exclude => "exclude",
+                };
+                format!("{} {}", action, criteria.join(", "))
+            }
+        };
+
+        Ok(Rule {
+            action: rule.action,
+            feed: rule.feed.as_deref().map(url_key),
+            keywords: rule.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            regex,
+            author: rule.author.as_ref().map(|author| author.to_lowercase()),
+            category: rule.category.clone(),
+            label,
+        })
+    }
+
+    fn matches(&self, article: &Article) -> bool {
+        let texts: Vec<&str> = article
+            .title
+            .iter()
+            .chain(article.summary.iter())
+            .map(String::as_str)
+            .collect();
+
+        let keywords = self.keywords.is_empty()
+            || texts.iter().any(|text| {
+                let text = text.to_lowercase();
+                self.keywords.iter().any(|keyword| text.contains(keyword))
+            });
+        let regex = self
+            .regex
+            .as_ref()
+            .is_none_or(|regex| texts.iter().any(|text| regex.is_match(text)));
+        let author = self.author.as_ref().is_none_or(|author| {
+            article
+                .author
+                .as_ref()
+                .is_some_and(|name| name.to_lowercase().contains(author))
+        });
+        let category = self.category.as_ref().is_none_or(|category| {
+            article
+                .categories
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(category))
+        });
+
+        keywords && regex && author && category
+    }
+}
+
+#[test]
+pub fn should_filter_articles() {
+    let config: Config = toml::from_str(
+        r#"
+        sendgrid_token = ""
+        mail_to = ""
+        report_type = "Stdout"
+
+        [[filters]]
+        action = "Exclude"
+        keywords = ["Sponsored"]
+
+        [[filters]]
+        action = "Include"
+        feed = "https://hnrss.org/frontpage"
+        regex = "(?i)\\brust\\b"
+
+        [[filters]]
+        action = "Include"
+        feed = "https://hnrss.org/frontpage"
+        category = "programming"
+        name = "programming"
+        "#,
+    )
+    .unwrap();
+    let filters = Filters::new(&config.filters).unwrap();
+
+    let feed = |url: &str| MonitoredFeed {
+        id: 1,
+        url: url.to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let article = |title: &str, category: &str| Article {
+        title: Some(title.to_string()),
+        summary: Some("Posted by someone".to_string()),
+        categories: vec![category.to_string()],
+        ..Default::default()
+    };
+    let articles = vec![
+        article("Rust 2019 roadmap", "news"),
+        article("Writing a compiler", "Programming"),
+        article("Trusting trust", "security"),
+        article("Sponsored: learn Rust", "ads"),
+        article("The history of Go", "news"),
+    ];
+
+    let (kept, suppressed) = filters.apply(&feed("https://hnrss.org/frontpage"), articles.clone());
+    let titles: Vec<String> = kept
+        .into_iter()
+        .filter_map(|article| article.title)
+        .collect();
+    assert_eq!(titles, vec!["Rust 2019 roadmap", "Writing a compiler"]);
+    assert_eq!(
+        suppressed,
+        vec![
+            (
+                "not matching include regex (?i)\\brust\\b or programming".to_string(),
+                2
+            ),
+            ("exclude keywords Sponsored".to_string(), 1),
+        ]
+    );
+
+    // the subscription may be written differently
+    let (kept, _) = filters.apply(&feed("http://HNRSS.org/frontpage/"), articles.clone());
+    assert_eq!(kept.len(), 2);
+
+    let (kept, suppressed) = filters.apply(&feed("https://blog.example.org/rss.xml"), articles);
+    assert_eq!(kept.len(), 4);
+    assert_eq!(
+        suppressed,
+        vec![("exclude keywords Sponsored".to_string(), 1)]
+    );
+}
+
+#[test]
+pub fn should_reject_invalid_filters() {
+    let rule = |regex: Option<&str>| FilterRule {
+        action: FilterAction::Exclude,
+        feed: None,
+        keywords: Vec::new(),
+        regex: regex.map(|regex| regex.to_string()),
+        author: None,
+        category: None,
+        name: None,
+    };
+
+    assert!(Filters::new(&[rule(Some("(unclosed"))]).is_err());
+    assert!(Filters::new(&[rule(None)]).is_err());
+    assert!(Filters::new(&[rule(Some("ok"))]).is_ok());
+}
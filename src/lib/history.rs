@@ -0,0 +1,280 @@
+use std::str::FromStr;
+
+use crate::common::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HistoryFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for HistoryFormat {
+    type Err = GoodMorningError;
+
+    fn from_str(format: &str) -> Result<HistoryFormat, GoodMorningError> {
+        match format.to_ascii_lowercase().as_str() {
+            "table" => Ok(HistoryFormat::Table),
+            "json" => Ok(HistoryFormat::Json),
+            _ => Err(GoodMorningError::InvalidArgument(format!(
+                "unknown format {:?}, expected table or json",
+                format
+            ))),
+        }
+    }
+}
+
+/// What happened to a subscription, as told by its stored checks and failures
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum HistoryEvent {
+    FirstCheck {
+        date: String,
+        title: String,
+        last_article: Option<String>,
+    },
+    TitleChange {
+        date: String,
+        from: String,
+        to: String,
+    },
+    ArticleChange {
+        date: String,
+        title: Option<String>,
+        guid: Option<String>,
+        pub_date: Option<String>,
+    },
+    /// Stored for a change of the feed publication date only
+    Check {
+        date: String,
+    },
+    Failure {
+        date: String,
+        error: String,
+    },
+}
+
+impl HistoryEvent {
+    fn date(&self) -> &str {
+        match self {
+            HistoryEvent::FirstCheck { date, .. }
+            | HistoryEvent::TitleChange { date, .. }
+            | HistoryEvent::ArticleChange { date, .. }
+            | HistoryEvent::Check { date }
+            | HistoryEvent::Failure { date, .. } => date,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            HistoryEvent::FirstCheck { .. } => "first check",
+            HistoryEvent::TitleChange { .. } => "title change",
+            HistoryEvent::ArticleChange { .. } => "article change",
+            HistoryEvent::Check { .. } => "check",
+            HistoryEvent::Failure { .. } => "failure",
+        }
+    }
+
+    fn details(&self) -> String {
+        match self {
+            HistoryEvent::FirstCheck {
+                title,
+                last_article,
+                ..
+            } => match last_article {
+                Some(article) => format!("{}, last article: {}", title, article),
+                None => title.clone(),
+            },
+            HistoryEvent::TitleChange { from, to, .. } => format!("{} -> {}", from, to),
+            HistoryEvent::ArticleChange { title, guid, .. } => title
+                .as_ref()
+                .or(guid.as_ref())
+                .map_or("Untitled".to_string(), String::clone),
+            HistoryEvent::Check { .. } => "feed updated".to_string(),
+            HistoryEvent::Failure { error, .. } => error.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct History<'a> {
+    url: &'a str,
+    events: &'a [HistoryEvent],
+}
+
+/// Print every stored check of a subscription, with the title and article changes they
+/// brought, and its failed checks.
+pub fn history(
+    repo: &dyn SubscriptionRepository,
+    url: &str,
+    format: HistoryFormat,
+) -> Result<(), GoodMorningError> {
+    info!("History of {}", url);
+
+    let feed = match super::find_subscription(repo, url) {
+        Some(feed) => feed,
+        None => {
+            println!("Not subscribed to {}", url);
+            return Ok(());
+        }
+    };
+
+    let events = history_events(&repo.get_checks(&feed), &repo.get_failures(&feed));
+
+    match format {
+        HistoryFormat::Json => {
+            let history = History {
+                url: &feed.url,
+                events: &events,
+            };
+            let json = serde_json::to_string_pretty(&history)
+                .map_err(|err| GoodMorningError::InvalidArgument(err.to_string()))?;
+            println!("{}", json);
+        }
+        HistoryFormat::Table => print!("{}", history_table(&events)),
+    }
+
+    Ok(())
+}
+
+// a check is stored when the feed changed, compared to the previous one
+fn history_events(checks: &[FeedCheckResult], failures: &[CheckFailure]) -> Vec<HistoryEvent> {
+    let mut events = Vec::new();
+    let mut previous: Option<&FeedCheckResult> = None;
+
+    for check in checks.iter() {
+        let date = check.check_date.clone();
+        let last = match previous {
+            Some(last) => last,
+            None => {
+                events.push(HistoryEvent::FirstCheck {
+                    date,
+                    title: check.title.clone(),
+                    last_article: check.last_article_title.clone(),
+                });
+                previous = Some(check);
+                continue;
+            }
+        };
+
+        let title_changed = last.title != check.title;
+        let article_changed = last.last_article_guid != check.last_article_guid
+            || last.last_article_hash != check.last_article_hash;
+
+        if title_changed {
+            events.push(HistoryEvent::TitleChange {
+                date: date.clone(),
+                from: last.title.clone(),
+                to: check.title.clone(),
+            });
+        }
+        if article_changed {
+            events.push(HistoryEvent::ArticleChange {
+                date: date.clone(),
+                title: check.last_article_title.clone(),
+                guid: check.last_article_guid.clone(),
+                pub_date: check.last_article_pub_date.clone(),
+            });
+        }
+        if !title_changed && !article_changed {
+            events.push(HistoryEvent::Check { date });
+        }
+
+        previous = Some(check);
+    }
+
+    events.extend(failures.iter().map(|failure| HistoryEvent::Failure {
+        date: failure.check_date.clone(),
+        error: failure.error.clone(),
+    }));
+
+    // stable, the changes of a check stay in order
+    events.sort_by(|a, b| a.date().cmp(b.date()));
+    events
+}
+
+fn history_table(events: &[HistoryEvent]) -> String {
+    if events.is_empty() {
+        return "Never checked\n".to_string();
+    }
+
+    let date_width = events
+        .iter()
+        .map(|event| event.date().len())
+        .max()
+        .unwrap_or(0);
+    let mut table = format!(
+        "{:<width$}  {:<14}  {}\n",
+        "DATE",
+        "EVENT",
+        "DETAILS",
+        width = date_width
+    );
+
+    for event in events.iter() {
+        table.push_str(&format!(
+            "{:<width$}  {:<14}  {}\n",
+            event.date(),
+            event.name(),
+            event.details(),
+            width = date_width
+        ));
+    }
+
+    table
+}
+
+#[test]
+pub fn should_tell_the_history_of_a_subscription() {
+    let check = |date: &str, title: &str, article: &str| FeedCheckResult {
+        check_date: date.to_string(),
+        title: title.to_string(),
+        pub_date: Some(date.to_string()),
+        last_article_title: Some(article.to_string()),
+        last_article_guid: Some(article.to_lowercase()),
+        last_article_pub_date: Some(date.to_string()),
+        last_article_hash: Some("None".to_string()),
+        articles: Vec::new(),
+    };
+    let checks = vec![
+        check("2019-03-01T07:00:00+00:00", "Liftoff News", "Star City"),
+        check("2019-03-02T07:00:00+00:00", "Liftoff News", "Sky watchers"),
+        check("2019-03-04T07:00:00+00:00", "Liftoff", "Sky watchers"),
+        check("2019-03-05T07:00:00+00:00", "Liftoff", "Sky watchers"),
+    ];
+    let failures = vec![CheckFailure {
+        check_date: "2019-03-03T07:00:00+00:00".to_string(),
+        error: "Http error: 503 Service Unavailable".to_string(),
+    }];
+
+    let events = history_events(&checks, &failures);
+
+    assert_eq!(
+        history_table(&events),
+        "DATE                       EVENT           DETAILS
+2019-03-01T07:00:00+00:00  first check     Liftoff News, last article: Star City
+2019-03-02T07:00:00+00:00  article change  Sky watchers
+2019-03-03T07:00:00+00:00  failure         Http error: 503 Service Unavailable
+2019-03-04T07:00:00+00:00  title change    Liftoff News -> Liftoff
+2019-03-05T07:00:00+00:00  check           feed updated
+"
+    );
+
+    let json = serde_json::to_value(&events[1..3]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "event": "article_change",
+                "date": "2019-03-02T07:00:00+00:00",
+                "title": "Sky watchers",
+                "guid": "sky watchers",
+                "pub_date": "2019-03-02T07:00:00+00:00"
+            },
+            {
+                "event": "failure",
+                "date": "2019-03-03T07:00:00+00:00",
+                "error": "Http error: 503 Service Unavailable"
+            }
+        ])
+    );
+}
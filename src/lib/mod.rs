@@ -1,135 +1,883 @@
-pub mod data;
-mod import;
-
-use crossbeam::crossbeam_channel::bounded;
-use std::collections::HashSet;
-use std::thread;
-
-use super::syndication::check_feed;
-use crate::common::*;
-use crate::notify::notify_updates;
-use import::read_csv;
-
-pub fn list_subscription(repo: &dyn SubscriptionRepository) {
-    info!("Listing subscriptions");
-
-    for feed in repo.get_monitored_feeds().into_iter() {
-        println!(
-            "{} (last update: {})",
-            feed.url,
-            feed.last_check
-                .as_ref()
-                .map_or("Never seen", |check| &check.check_date)
-        );
-    }
-}
-
-pub fn import_subscriptions(repo: &dyn SubscriptionRepository, file_path: &str) {
-    info!("Importing {} to db", file_path);
-
-    let csv_feeds = read_csv(file_path);
-
-    let existing_feeds: HashSet<(String, FeedType)> = repo
-        .get_monitored_feeds()
-        .into_iter()
-        .map(|feed| (feed.url, feed.kind))
-        .collect();
-
-    for (url, kind) in csv_feeds.difference(&existing_feeds) {
-        println!("Adding new feed {}", url);
-        repo.add_sub(url, *kind);
-    }
-}
-
-// TODO from cli
-const PARALLEL_DOWNLOAD_MAX: usize = 4;
-
-pub fn run(
-    repo: &dyn SubscriptionRepository,
-    dry_run: bool,
-    config: &Config,
-) -> Result<(), GoodMorningError> {
-    info!("Run (dry: {:?})", dry_run);
-
-    let (dl_chan_s, dl_chan_r) = bounded(PARALLEL_DOWNLOAD_MAX * 2);
-    let (storage_chan_s, storage_chan_r) = bounded(PARALLEL_DOWNLOAD_MAX * 2);
-
-    for i in 0..PARALLEL_DOWNLOAD_MAX {
-        let my_dl = dl_chan_r.clone();
-        let my_storage = storage_chan_s.clone();
-
-        debug!("Starting dl thread {:?}", i);
-        thread::spawn(move || loop {
-            match my_dl.recv() {
-                Ok(feed) => {
-                    let check_result = check_feed(&feed);
-                    my_storage.send((feed, check_result)).unwrap();
-                }
-                Err(_err) => {
-                    drop(my_storage);
-                    break;
-                }
-            }
-        });
-    }
-
-    drop(storage_chan_s);
-
-    for feed in repo.get_monitored_feeds().into_iter() {
-        dl_chan_s.send(feed).unwrap();
-    }
-
-    drop(dl_chan_s);
-
-    let mut results = Vec::new();
-    while let Ok((feed, check_result)) = storage_chan_r.recv() {
-        let update_kind = process_feed(repo, &feed, &check_result);
-        if let Some(update_kind) = update_kind {
-            results.push((feed, update_kind));
-        }
-    }
-
-    notify_updates(config, results)?;
-    Ok(())
-}
-
-fn process_feed(
-    repo: &dyn SubscriptionRepository,
-    feed: &MonitoredFeed,
-    check_result: &Option<FeedCheckResult>,
-) -> Option<FeedUpdateKind> {
-    let check_result = match check_result {
-        None => {
-            warn!("Feed without result {:?}", feed.url);
-            return None;
-        }
-        Some(r) => r,
-    };
-    let update_kind = get_update_kind(&feed, &check_result);
-
-    //store
-    match update_kind {
-        None => (),
-        Some(_) => repo.add_check(&feed, &check_result),
-    }
-
-    update_kind
-}
-
-fn get_update_kind(feed: &MonitoredFeed, check_result: &FeedCheckResult) -> Option<FeedUpdateKind> {
-    match feed.last_check {
-        None => Some(FeedUpdateKind::FirstCheck),
-        Some(ref last_check) if last_check.title != check_result.title => {
-            Some(FeedUpdateKind::Title)
-        }
-        Some(ref last_check) if last_check.last_article_guid != check_result.last_article_guid => {
-            Some(FeedUpdateKind::NewArticle)
-        }
-        Some(ref last_check)
-            if last_check.last_article_pub_date != check_result.last_article_pub_date =>
-        {
-            Some(FeedUpdateKind::LastArticle)
-        }
-        _ => None,
-    }
-}
+mod archive;
+pub mod data;
+mod dedup;
+mod enclosure;
+mod filter;
+mod history;
+mod import;
+mod rewrite;
+mod search;
+
+use chrono::Utc;
+use crossbeam::crossbeam_channel::{bounded, unbounded};
+use failure::Fail;
+use reqwest::StatusCode;
+use std::collections::{HashSet, VecDeque};
+use std::thread;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::syndication::{
+    check_feed, clean_url, discover_feeds, fetch_full_text, parse_date, url_key, CheckContext,
+    FeedCandidate,
+};
+use crate::common::*;
+use crate::http::http_status;
+use crate::notify::{notify_updates, print_updates};
+use archive::archive_articles;
+use dedup::{article_key, deduplicate};
+pub use enclosure::download_enclosures;
+use filter::Filters;
+pub use history::{history, HistoryFormat};
+use import::read_csv;
+use rewrite::LinkRewriter;
+pub use search::search;
+
+pub fn list_subscription(repo: &dyn SubscriptionRepository) {
+    info!("Listing subscriptions");
+
+    for feed in repo.get_monitored_feeds().into_iter() {
+        let last_update = feed
+            .last_check
+            .as_ref()
+            .map_or("Never seen", |check| &check.check_date);
+        match feed.retired {
+            Some(ref reason) => println!(
+                "{} (last update: {}, retired: {})",
+                feed.url, last_update, reason
+            ),
+            None => println!("{} (last update: {})", feed.url, last_update),
+        }
+    }
+}
+
+pub fn import_subscriptions(repo: &dyn SubscriptionRepository, file_path: &str) {
+    info!("Importing {} to db", file_path);
+
+    let csv_feeds = read_csv(file_path);
+
+    let mut existing_feeds: HashSet<String> = repo
+        .get_monitored_feeds()
+        .iter()
+        .map(|feed| url_key(&feed.url))
+        .collect();
+
+    for (url, kind) in csv_feeds.iter() {
+        if existing_feeds.insert(url_key(url)) {
+            let url = clean_url(url);
+            println!("Adding new feed {}", url);
+            repo.add_sub(&url, *kind);
+        }
+    }
+}
+
+pub fn discover(url: &str, config: &Config) -> Result<(), GoodMorningError> {
+    info!("Discovering feeds of {}", url);
+
+    let candidates = discover_feeds(&CheckContext::new(config)?, url, &FeedAccess::default())?;
+    print_candidates(&candidates);
+
+    Ok(())
+}
+
+/// Subscribe to `url`, or to one of the feeds it links to.
+/// When several feeds are found, `pick` is the index of the one to subscribe to.
+pub fn add_subscription(
+    repo: &dyn SubscriptionRepository,
+    url: &str,
+    pick: Option<usize>,
+    access: &FeedAccess,
+    full_text: bool,
+    config: &Config,
+) -> Result<(), GoodMorningError> {
+    info!("Adding subscription to {}", url);
+
+    let candidates: Vec<FeedCandidate> = discover_feeds(&CheckContext::new(config)?, url, access)?
+        .into_iter()
+        .filter(|candidate| candidate.kind().is_some())
+        .collect();
+
+    let candidate = match (pick, candidates.len()) {
+        (_, 0) => {
+            println!("No supported feed found for {}", url);
+            return Ok(());
+        }
+        (None, 1) => &candidates[0],
+        (Some(index), len) if index < len => &candidates[index],
+        _ => {
+            print_candidates(&candidates);
+            println!("Several feeds found, choose one with --pick <index>");
+            return Ok(());
+        }
+    };
+
+    let kind = candidate.kind().unwrap();
+    let url = clean_url(&candidate.url);
+
+    if let Some(feed) = find_subscription(repo, &url) {
+        println!("Already subscribed to {}", feed.url);
+        return Ok(());
+    }
+
+    println!("Adding new feed {}", url);
+    repo.add_sub(&url, kind);
+
+    if *access != FeedAccess::default() {
+        set_access(repo, &url, access)?;
+    }
+    if full_text {
+        set_full_text(repo, &url, full_text);
+    }
+
+    Ok(())
+}
+
+/// Replace the credential and the headers a subscription is requested with
+pub fn set_access(
+    repo: &dyn SubscriptionRepository,
+    url: &str,
+    access: &FeedAccess,
+) -> Result<(), GoodMorningError> {
+    match find_subscription(repo, url) {
+        Some(feed) => repo.set_access(&feed, access),
+        None => println!("Not subscribed to {}", url),
+    }
+
+    Ok(())
+}
+
+/// Whether the pages of the new articles of a subscription are downloaded to extract their text
+pub fn set_full_text(repo: &dyn SubscriptionRepository, url: &str, full_text: bool) {
+    match find_subscription(repo, url) {
+        Some(feed) => repo.set_full_text(&feed, full_text),
+        None => println!("Not subscribed to {}", url),
+    }
+}
+
+fn find_subscription(repo: &dyn SubscriptionRepository, url: &str) -> Option<MonitoredFeed> {
+    let key = url_key(url);
+    repo.get_monitored_feeds()
+        .into_iter()
+        .find(|feed| url_key(&feed.url) == key)
+}
+
+/// Merge the subscriptions to the same feed written differently (case, trailing slash, http
+/// and https...) into the oldest one, which gets the https url of one of them and their
+/// credential, headers and full text setting. Subscriptions requested differently aren't merged.
+pub fn dedupe_subscriptions(repo: &dyn SubscriptionRepository) {
+    info!("Deduplicating subscriptions");
+
+    let mut groups: Vec<(String, Vec<MonitoredFeed>)> = Vec::new();
+    for feed in repo.get_monitored_feeds().into_iter() {
+        let key = url_key(&feed.url);
+        match groups.iter_mut().find(|(known, _)| *known == key) {
+            Some((_, feeds)) => feeds.push(feed),
+            None => groups.push((key, vec![feed])),
+        }
+    }
+
+    for (_, mut feeds) in groups.into_iter() {
+        feeds.sort_by_key(|feed| feed.id);
+        let mut feeds = feeds.into_iter();
+        let mut kept = match feeds.next() {
+            Some(feed) => feed,
+            None => continue,
+        };
+        let mut urls = vec![clean_url(&kept.url)];
+
+        for duplicate in feeds {
+            let public = FeedAccess::default();
+            if duplicate.access != public
+                && kept.access != public
+                && duplicate.access != kept.access
+            {
+                println!(
+                    "Not merging {} into {}, they're requested with different credentials or headers",
+                    duplicate.url, kept.url
+                );
+                continue;
+            }
+
+            println!("Merging {} into {}", duplicate.url, kept.url);
+            repo.merge_sub(&kept, &duplicate);
+
+            // the settings of the duplicate are kept too
+            if kept.access == public && duplicate.access != public {
+                repo.set_access(&kept, &duplicate.access);
+                kept.access = duplicate.access.clone();
+            }
+            if duplicate.full_text && !kept.full_text {
+                repo.set_full_text(&kept, true);
+                kept.full_text = true;
+            }
+
+            urls.push(clean_url(&duplicate.url));
+        }
+
+        // https is kept when one of the merged duplicates has it
+        let url = urls
+            .iter()
+            .find(|url| url.starts_with("https://"))
+            .unwrap_or(&urls[0]);
+
+        if kept.url != *url {
+            println!("Renaming {} to {}", kept.url, url);
+            repo.set_url(&kept, url);
+        }
+    }
+}
+
+/// Access given on the command line, `headers` are `Name: value` strings
+pub fn parse_access(
+    credential: &Option<String>,
+    headers: &[String],
+    config: &Config,
+) -> Result<FeedAccess, GoodMorningError> {
+    if let Some(ref name) = credential {
+        if !config.credentials.contains_key(name) {
+            return Err(GoodMorningError::InvalidConfig(format!(
+                "unknown credential {:?}",
+                name
+            )));
+        }
+    }
+
+    let headers = headers
+        .iter()
+        .map(|header| {
+            parse_header(header).ok_or_else(|| {
+                GoodMorningError::InvalidConfig(format!("invalid header {:?}", header))
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(FeedAccess {
+        credential: credential.clone(),
+        headers,
+    })
+}
+
+fn print_candidates(candidates: &[FeedCandidate]) {
+    if candidates.is_empty() {
+        println!("No feed found");
+    }
+
+    for (index, candidate) in candidates.iter().enumerate() {
+        let kind = candidate
+            .kind()
+            .map_or("unsupported".to_string(), |kind| kind.to_string());
+        match candidate.title {
+            Some(ref title) => println!("[{}] {} {} ({})", index, kind, candidate.url, title),
+            None => println!("[{}] {} {}", index, kind, candidate.url),
+        }
+    }
+}
+
+/// Check every feed and notify their new articles. A `dry_run` only prints the report: nothing
+/// is stored, archived nor sent.
+pub fn run(
+    repo: &dyn SubscriptionRepository,
+    dry_run: bool,
+    config: &Config,
+) -> Result<(), GoodMorningError> {
+    info!("Run (dry: {:?})", dry_run);
+
+    let context = CheckContext::new(config)?;
+    let filters = Filters::new(&config.filters)?;
+    let rewriter = LinkRewriter::new(config)?;
+    let workers = config.workers.max(1);
+    let deadline = config
+        .run_deadline
+        .map(|seconds| Instant::now() + Duration::from_secs(seconds));
+
+    // unbounded so that queuing every feed never waits on the workers
+    let (dl_chan_s, dl_chan_r) = unbounded();
+    let (storage_chan_s, storage_chan_r) = bounded(workers * 2);
+
+    for i in 0..workers {
+        let my_dl = dl_chan_r.clone();
+        let my_storage = storage_chan_s.clone();
+        let my_context = context.clone();
+
+        debug!("Starting dl thread {:?}", i);
+        thread::spawn(move || {
+            while let Ok(feed) = my_dl.recv() {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    break;
+                }
+
+                let check_result = check_feed(&my_context, &feed);
+                // the run stops listening once the deadline is reached
+                if my_storage.send((feed, check_result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    drop(storage_chan_s);
+
+    let feeds = interleave_hosts(
+        repo.get_monitored_feeds()
+            .into_iter()
+            .filter(|feed| feed.retired.is_none())
+            .collect(),
+    );
+    let mut unchecked = feeds.len();
+    for feed in feeds.into_iter() {
+        dl_chan_s.send(feed).unwrap();
+    }
+
+    drop(dl_chan_s);
+
+    let mut results = Vec::new();
+    loop {
+        let received = match deadline {
+            Some(deadline) => storage_chan_r
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok(),
+            None => storage_chan_r.recv().ok(),
+        };
+        let (feed, check_result) = match received {
+            Some(received) => received,
+            None => break,
+        };
+        unchecked -= 1;
+
+        match check_result {
+            // deferred to the next run, the feed wasn't checked
+            Ok(_) | Err(GoodMorningError::HostBackOff(_)) => (),
+            Err(_) if dry_run => (),
+            Err(ref err) => repo.add_failure(
+                &feed,
+                &CheckFailure {
+                    check_date: Utc::now().to_rfc3339(),
+                    error: error_message(err),
+                },
+            ),
+        }
+
+        let status = check_result.as_ref().err().and_then(http_status);
+        if let Some(reason) = retirement_reason(&feed, status, config.retire_after_not_found) {
+            warn!("Retiring {:?}, it {}", feed.url, reason);
+            if !dry_run {
+                repo.retire_sub(&feed, &reason);
+            }
+            results.push(FeedUpdate {
+                feed: MonitoredFeed {
+                    retired: Some(reason),
+                    ..feed
+                },
+                kind: FeedUpdateKind::Retired,
+                new_articles: Vec::new(),
+                suppressed: Vec::new(),
+            });
+            continue;
+        }
+
+        let not_found_streak = match status {
+            Some(StatusCode::NOT_FOUND) => feed.not_found_streak + 1,
+            _ if check_result.is_ok() => 0,
+            _ => feed.not_found_streak,
+        };
+        if not_found_streak != feed.not_found_streak && !dry_run {
+            repo.set_not_found_streak(&feed, not_found_streak);
+        }
+
+        let update_kind = process_feed(repo, &feed, &check_result, dry_run);
+        if let (Some(kind), Ok(check_result)) = (update_kind, check_result) {
+            let new_articles = get_new_articles(&feed, kind, &check_result);
+            let (new_articles, suppressed) = filters.apply(&feed, new_articles);
+            let new_articles = rewriter.apply(&context, new_articles);
+            let new_articles = if feed.full_text && !dry_run {
+                fetch_full_texts(&context, new_articles)
+            } else {
+                new_articles
+            };
+            let new_articles = match config.archive_dir {
+                Some(ref dir) if !dry_run => {
+                    archive_articles(&context, dir, &feed, &check_result.title, new_articles)
+                }
+                _ => new_articles,
+            };
+            results.push(FeedUpdate {
+                feed,
+                kind,
+                new_articles,
+                suppressed,
+            });
+        }
+    }
+
+    if unchecked > 0 {
+        warn!("Run deadline reached, {} feeds left unchecked", unchecked);
+    }
+
+    let since = Utc::now() - chrono::Duration::days(i64::from(config.dedup_days));
+    deduplicate(&mut results, &repo.get_article_keys(&since.to_rfc3339()));
+
+    // nothing is sent nor stored, the report is only shown
+    if dry_run {
+        print_updates(&results);
+        return Ok(());
+    }
+
+    notify_updates(config, &context.client, &results)?;
+
+    for update in results.iter() {
+        for article in update.new_articles.iter() {
+            repo.add_article(&update.feed, article, &article_key(article));
+        }
+    }
+
+    Ok(())
+}
+
+// the error and its causes, like `Http error: 404 Not Found`
+fn error_message(err: &GoodMorningError) -> String {
+    let mut message = err.to_string();
+    let mut cause = err.cause();
+    while let Some(inner) = cause {
+        message.push_str(&format!(": {}", inner));
+        cause = inner.cause();
+    }
+    message
+}
+
+fn process_feed(
+    repo: &dyn SubscriptionRepository,
+    feed: &MonitoredFeed,
+    check_result: &Result<FeedCheckResult, GoodMorningError>,
+    dry_run: bool,
+) -> Option<FeedUpdateKind> {
+    let check_result = match check_result {
+        Err(_) => {
+            warn!("Feed without result {:?}", feed.url);
+            return None;
+        }
+        Ok(r) => r,
+    };
+    let update_kind = get_update_kind(&feed, &check_result);
+
+    //store
+    match update_kind {
+        None => (),
+        Some(_) if dry_run => (),
+        Some(_) => repo.add_check(&feed, &check_result),
+    }
+
+    update_kind
+}
+
+fn get_update_kind(feed: &MonitoredFeed, check_result: &FeedCheckResult) -> Option<FeedUpdateKind> {
+    match feed.last_check {
+        None => Some(FeedUpdateKind::FirstCheck),
+        Some(ref last_check) if last_check.title != check_result.title => {
+            Some(FeedUpdateKind::Title)
+        }
+        Some(ref last_check) if last_check.last_article_guid != check_result.last_article_guid => {
+            Some(FeedUpdateKind::NewArticle)
+        }
+        Some(ref last_check)
+            if last_check.last_article_pub_date != check_result.last_article_pub_date =>
+        {
+            Some(FeedUpdateKind::LastArticle)
+        }
+        _ => None,
+    }
+}
+
+/// Why a feed failing with `status` should no longer be checked: the publisher answered
+/// 410 Gone, or 404 for `retire_after_not_found` runs in a row.
+fn retirement_reason(
+    feed: &MonitoredFeed,
+    status: Option<StatusCode>,
+    retire_after_not_found: Option<u32>,
+) -> Option<String> {
+    match status {
+        Some(StatusCode::GONE) => Some("answered 410 Gone".to_string()),
+        Some(StatusCode::NOT_FOUND) => {
+            let streak = feed.not_found_streak + 1;
+            retire_after_not_found
+                .filter(|max| streak >= *max)
+                .map(|_| format!("answered 404 Not Found {} runs in a row", streak))
+        }
+        _ => None,
+    }
+}
+
+// articles whose page can't be downloaded are kept without their text
+fn fetch_full_texts(context: &CheckContext, articles: Vec<Article>) -> Vec<Article> {
+    articles
+        .into_iter()
+        .map(|article| {
+            let content =
+                article
+                    .link
+                    .as_ref()
+                    .and_then(|link| match fetch_full_text(context, link) {
+                        Ok(content) => content,
+                        Err(err) => {
+                            warn!("Could not fetch the full text of {:?} ({:?})", link, err);
+                            None
+                        }
+                    });
+            Article { content, ..article }
+        })
+        .collect()
+}
+
+/// Spread the feeds of each host over the run, a host is only requested by one worker at a time
+fn interleave_hosts(feeds: Vec<MonitoredFeed>) -> Vec<MonitoredFeed> {
+    let mut by_host: Vec<(Option<String>, VecDeque<MonitoredFeed>)> = Vec::new();
+
+    for feed in feeds.into_iter() {
+        let host = Url::parse(&feed.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()));
+        match by_host.iter_mut().find(|(known, _)| *known == host) {
+            Some((_, host_feeds)) => host_feeds.push_back(feed),
+            None => by_host.push((host, vec![feed].into())),
+        }
+    }
+
+    let mut interleaved = Vec::new();
+    while !by_host.is_empty() {
+        for (_, host_feeds) in by_host.iter_mut() {
+            interleaved.extend(host_feeds.pop_front());
+        }
+        by_host.retain(|(_, host_feeds)| !host_feeds.is_empty());
+    }
+
+    interleaved
+}
+
+/// Articles published since the last check, the checked articles are sorted newest first.
+fn get_new_articles(
+    feed: &MonitoredFeed,
+    update_kind: FeedUpdateKind,
+    check_result: &FeedCheckResult,
+) -> Vec<Article> {
+    let articles = &check_result.articles;
+
+    let last_check = match (update_kind, &feed.last_check) {
+        (FeedUpdateKind::FirstCheck, _) | (_, None) => {
+            return articles.iter().take(1).cloned().collect()
+        }
+        (_, Some(last_check)) => last_check,
+    };
+
+    if let Some(ref last_guid) = last_check.last_article_guid {
+        if let Some(position) = articles
+            .iter()
+            .position(|article| article.guid.as_ref() == Some(last_guid))
+        {
+            return articles[..position].to_vec();
+        }
+    }
+
+    match last_check
+        .last_article_pub_date
+        .as_ref()
+        .and_then(|date| parse_date(date))
+    {
+        Some(last_date) => articles
+            .iter()
+            .filter(|article| {
+                article
+                    .pub_date
+                    .as_ref()
+                    .and_then(|date| parse_date(date))
+                    .is_some_and(|date| date > last_date)
+            })
+            .cloned()
+            .collect(),
+        None => match update_kind {
+            FeedUpdateKind::NewArticle => articles.iter().take(1).cloned().collect(),
+            _ => Vec::new(),
+        },
+    }
+}
+
+#[test]
+pub fn should_list_articles_newer_than_last_check() {
+    let article = |guid: &str, date: &str| Article {
+        guid: Some(guid.to_string()),
+        pub_date: Some(date.to_string()),
+        ..Default::default()
+    };
+    let check_result = |articles: Vec<Article>| FeedCheckResult {
+        check_date: "2019-01-10T00:00:00+00:00".to_string(),
+        title: "Hiking Treks".to_string(),
+        pub_date: None,
+        last_article_title: None,
+        last_article_guid: articles[0].guid.clone(),
+        last_article_pub_date: articles[0].pub_date.clone(),
+        last_article_hash: None,
+        articles,
+    };
+
+    let mut feed = MonitoredFeed {
+        id: 1,
+        url: "https://example.com/feed.xml".to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+
+    let first = check_result(vec![
+        article("b", "2019-01-08T00:00:00Z"),
+        article("a", "2019-01-01T00:00:00Z"),
+    ]);
+    let new_articles = get_new_articles(&feed, FeedUpdateKind::FirstCheck, &first);
+    assert_eq!(new_articles.len(), 1);
+
+    feed.last_check = Some(first);
+
+    let second = check_result(vec![
+        article("d", "2019-01-10T00:00:00Z"),
+        article("c", "2019-01-09T00:00:00Z"),
+        article("b", "2019-01-08T00:00:00Z"),
+    ]);
+    let new_articles = get_new_articles(&feed, FeedUpdateKind::NewArticle, &second);
+    let guids: Vec<_> = new_articles.iter().filter_map(|a| a.guid.clone()).collect();
+    assert_eq!(guids, vec!["d", "c"]);
+
+    // the last seen article is gone from the feed
+    let third = check_result(vec![
+        article("e", "2019-01-11T00:00:00Z"),
+        article("d", "2019-01-07T00:00:00Z"),
+    ]);
+    let new_articles = get_new_articles(&feed, FeedUpdateKind::NewArticle, &third);
+    let guids: Vec<_> = new_articles.iter().filter_map(|a| a.guid.clone()).collect();
+    assert_eq!(guids, vec!["e"]);
+}
+
+#[test]
+pub fn should_retire_gone_feeds() {
+    let mut feed = MonitoredFeed {
+        id: 1,
+        url: "http://example.org/feed.xml".to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+
+    assert_eq!(
+        retirement_reason(&feed, Some(StatusCode::GONE), None),
+        Some("answered 410 Gone".to_string())
+    );
+    assert_eq!(retirement_reason(&feed, None, Some(1)), None);
+    assert_eq!(
+        retirement_reason(&feed, Some(StatusCode::INTERNAL_SERVER_ERROR), Some(1)),
+        None
+    );
+    assert_eq!(
+        retirement_reason(&feed, Some(StatusCode::NOT_FOUND), None),
+        None
+    );
+
+    assert_eq!(
+        retirement_reason(&feed, Some(StatusCode::NOT_FOUND), Some(3)),
+        None
+    );
+    feed.not_found_streak = 2;
+    assert_eq!(
+        retirement_reason(&feed, Some(StatusCode::NOT_FOUND), Some(3)),
+        Some("answered 404 Not Found 3 runs in a row".to_string())
+    );
+}
+
+#[test]
+pub fn should_interleave_feeds_of_the_same_host() {
+    let feed = |url: &str| MonitoredFeed {
+        id: 0,
+        url: url.to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let feeds = vec![
+        feed("https://github.com/a.atom"),
+        feed("https://github.com/b.atom"),
+        feed("https://github.com/c.atom"),
+        feed("https://medium.com/feed/a"),
+        feed("https://blog.example.org/rss.xml"),
+    ];
+
+    let urls: Vec<String> = interleave_hosts(feeds)
+        .into_iter()
+        .map(|feed| feed.url)
+        .collect();
+
+    assert_eq!(
+        urls,
+        vec![
+            "https://github.com/a.atom",
+            "https://medium.com/feed/a",
+            "https://blog.example.org/rss.xml",
+            "https://github.com/b.atom",
+            "https://github.com/c.atom",
+        ]
+    );
+}
+
+#[test]
+pub fn should_merge_duplicate_subscriptions() {
+    let repo = data::SQliteSubscriptionRepository::new(":memory:");
+    repo.init();
+
+    repo.add_sub("http://x.com/feed", FeedType::Rss);
+    repo.add_sub("https://x.com/feed/", FeedType::Rss);
+    repo.add_sub("HTTPS://X.com/feed?utm_source=csv", FeedType::Rss);
+    repo.add_sub("https://y.com/feed", FeedType::Atom);
+
+    let check = |date: &str, title: &str| FeedCheckResult {
+        check_date: date.to_string(),
+        title: title.to_string(),
+        pub_date: None,
+        last_article_title: Some(title.to_string()),
+        last_article_guid: Some(title.to_string()),
+        last_article_pub_date: Some(date.to_string()),
+        last_article_hash: Some("None".to_string()),
+        articles: Vec::new(),
+    };
+    let feeds = repo.get_monitored_feeds();
+    repo.add_check(&feeds[0], &check("2019-01-01T00:00:00+00:00", "First"));
+    repo.add_check(&feeds[1], &check("2019-01-02T00:00:00+00:00", "Second"));
+    let failure = CheckFailure {
+        check_date: "2019-01-03T00:00:00+00:00".to_string(),
+        error: "Http error".to_string(),
+    };
+    repo.add_failure(&feeds[2], &failure);
+
+    dedupe_subscriptions(&repo);
+
+    let mut feeds = repo.get_monitored_feeds();
+    feeds.sort_by_key(|feed| feed.id);
+    let urls: Vec<&str> = feeds.iter().map(|feed| feed.url.as_str()).collect();
+    assert_eq!(urls, vec!["https://x.com/feed/", "https://y.com/feed"]);
+    assert_eq!(feeds[0].last_check.as_ref().unwrap().title, "Second");
+    let titles: Vec<String> = repo
+        .get_checks(&feeds[0])
+        .into_iter()
+        .map(|check| check.title)
+        .collect();
+    assert_eq!(titles, vec!["First", "Second"]);
+    assert_eq!(repo.get_failures(&feeds[0]), vec![failure]);
+}
+
+#[test]
+pub fn should_keep_the_settings_of_merged_subscriptions() {
+    let repo = data::SQliteSubscriptionRepository::new(":memory:");
+    repo.init();
+
+    repo.add_sub("http://x.com/feed", FeedType::Rss);
+    repo.add_sub("https://x.com/feed/", FeedType::Rss);
+    repo.add_sub("HTTPS://X.com/feed", FeedType::Rss);
+    let access = |credential: &str| FeedAccess {
+        credential: Some(credential.to_string()),
+        headers: Vec::new(),
+    };
+    let feeds = repo.get_monitored_feeds();
+    repo.set_access(&feeds[1], &access("x"));
+    repo.set_full_text(&feeds[1], true);
+    repo.set_access(&feeds[2], &access("other"));
+
+    dedupe_subscriptions(&repo);
+
+    let mut feeds = repo.get_monitored_feeds();
+    feeds.sort_by_key(|feed| feed.id);
+    let urls: Vec<&str> = feeds.iter().map(|feed| feed.url.as_str()).collect();
+    assert_eq!(urls, vec!["https://x.com/feed/", "HTTPS://X.com/feed"]);
+    assert_eq!(feeds[0].access, access("x"));
+    assert!(feeds[0].full_text);
+    assert_eq!(feeds[1].access, access("other"));
+}
+
+#[test]
+pub fn should_run_against_local_feeds() {
+    let repo = data::SQliteSubscriptionRepository::new(":memory:");
+    repo.init();
+    repo.add_sub("fixtures/feeds/liftoff.rss", FeedType::Rss);
+    repo.add_sub(
+        concat!(
+            "file://",
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/feeds/example.atom"
+        ),
+        FeedType::Atom,
+    );
+
+    let config: Config = toml::from_str(
+        r#"
+            sendgrid_token = ""
+            mail_to = ""
+            report_type = "Stdout"
+        "#,
+    )
+    .unwrap();
+
+    run(&repo, false, &config).unwrap();
+
+    let feeds = repo.get_monitored_feeds();
+    assert_eq!(feeds.len(), 2);
+
+    let rss_check = feeds[0].last_check.as_ref().unwrap();
+    assert_eq!(rss_check.title, "Liftoff News");
+    assert_eq!(
+        rss_check.last_article_guid,
+        Some("http://liftoff.msfc.nasa.gov/2003/05/27.html#item571".to_string())
+    );
+
+    let atom_check = feeds[1].last_check.as_ref().unwrap();
+    assert_eq!(atom_check.title, "Example Feed");
+    assert_eq!(
+        atom_check.last_article_title,
+        Some("Atom-Powered Robots Run Amok".to_string())
+    );
+
+    // nothing changed, nothing stored
+    run(&repo, false, &config).unwrap();
+    let feeds = repo.get_monitored_feeds();
+    assert_eq!(
+        feeds[0].last_check.as_ref().unwrap().check_date,
+        rss_check.check_date
+    );
+}
+
+#[test]
+pub fn should_not_store_anything_on_dry_runs() {
+    let repo = data::SQliteSubscriptionRepository::new(":memory:");
+    repo.init();
+    repo.add_sub("fixtures/feeds/liftoff.rss", FeedType::Rss);
+    repo.add_sub("fixtures/feeds/missing.rss", FeedType::Rss);
+
+    let config: Config = toml::from_str(
+        r#"
+            sendgrid_token = ""
+            mail_to = ""
+            report_type = "Email"
+        "#,
+    )
+    .unwrap();
+
+    run(&repo, true, &config).unwrap();
+
+    for feed in repo.get_monitored_feeds().iter() {
+        assert!(feed.last_check.is_none());
+        assert!(repo.get_failures(feed).is_empty());
+    }
+    assert!(repo
+        .get_article_keys("2000-01-01T00:00:00+00:00")
+        .is_empty());
+}
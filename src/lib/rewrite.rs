@@ -0,0 +1,154 @@
+use regex::Regex;
+use url::Url;
+
+use crate::common::*;
+use crate::syndication::{
+    is_feedburner_redirect, resolve_redirect, strip_tracking_params, CheckContext,
+};
+
+/// The link rewrites of the config, applied to the new articles before they're stored or notified
+pub struct LinkRewriter {
+    strip_tracking_params: bool,
+    expand_redirects: bool,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    domain: String,
+    pattern: Regex,
+    replace: String,
+}
+
+impl LinkRewriter {
+    pub fn new(config: &Config) -> Result<LinkRewriter, GoodMorningError> {
+        let rules = config
+            .link_rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern).map_err(|err| {
+                    GoodMorningError::InvalidConfig(format!(
+                        "invalid link rule pattern {:?} ({})",
+                        rule.pattern, err
+                    ))
+                })?;
+                Ok(Rule {
+                    domain: rule.domain.trim().trim_start_matches('.').to_lowercase(),
+                    pattern,
+                    replace: rule.replace.clone(),
+                })
+            })
+            .collect::<Result<_, GoodMorningError>>()?;
+
+        Ok(LinkRewriter {
+            strip_tracking_params: config.strip_tracking_params,
+            expand_redirects: config.expand_redirects,
+            rules,
+        })
+    }
+
+    pub fn apply(&self, context: &CheckContext, articles: Vec<Article>) -> Vec<Article> {
+        articles
+            .into_iter()
+            .map(|article| Article {
+                link: article.link.as_ref().map(|link| {
+                    let link = self.expand(context, link);
+                    self.rewrite(&link)
+                }),
+                ..article
+            })
+            .collect()
+    }
+
+    // the link is kept as is when the redirection can't be followed
+    fn expand(&self, context: &CheckContext, link: &str) -> String {
+        if !self.expand_redirects || !is_feedburner_redirect(link) {
+            return link.to_string();
+        }
+
+        match resolve_redirect(context, link) {
+            Ok(target) => {
+                debug!("Expanded {:?} to {:?}", link, target);
+                target
+            }
+            Err(err) => {
+                warn!("Could not expand {:?} ({:?})", link, err);
+                link.to_string()
+            }
+        }
+    }
+
+    fn rewrite(&self, link: &str) -> String {
+        let mut link = if self.strip_tracking_params {
+            strip_tracking_params(link)
+        } else {
+            link.to_string()
+        };
+
+        for rule in self.rules.iter() {
+            let host = Url::parse(&link)
+                .ok()
+                .and_then(|url| url.host_str().map(|host| host.to_lowercase()));
+            if host.is_some_and(|host| rule.applies_to(&host)) {
+                link = rule
+                    .pattern
+                    .replace(&link, rule.replace.as_str())
+                    .into_owned();
+            }
+        }
+
+        link
+    }
+}
+
+impl Rule {
+    fn applies_to(&self, host: &str) -> bool {
+        host == self.domain || host.ends_with(&format!(".{}", self.domain))
+    }
+}
+
+#[test]
+pub fn should_rewrite_links() {
+    let config: Config = toml::from_str(
+        r#"
+        sendgrid_token = ""
+        mail_to = ""
+        report_type = "Stdout"
+
+        [[link_rules]]
+        domain = "medium.com"
+        pattern = "^https://([a-z]+\\.)?medium\\.com/"
+        replace = "https://scribe.rip/"
+
+        [[link_rules]]
+        domain = "example.org"
+        pattern = "/amp/?$"
+        replace = ""
+        "#,
+    )
+    .unwrap();
+    let rewriter = LinkRewriter::new(&config).unwrap();
+
+    assert_eq!(
+        rewriter.rewrite("https://blog.medium.com/some-post-1a2b?utm_source=rss&source=feed"),
+        "https://scribe.rip/some-post-1a2b?source=feed"
+    );
+    assert_eq!(
+        rewriter.rewrite("https://example.org/2019/post/amp/?fbclid=abc"),
+        "https://example.org/2019/post"
+    );
+    assert_eq!(
+        rewriter.rewrite("https://notexample.org/2019/post/amp"),
+        "https://notexample.org/2019/post/amp"
+    );
+
+    let config = Config {
+        strip_tracking_params: false,
+        link_rules: Vec::new(),
+        ..config
+    };
+    let rewriter = LinkRewriter::new(&config).unwrap();
+    assert_eq!(
+        rewriter.rewrite("https://example.org/post?utm_source=rss"),
+        "https://example.org/post?utm_source=rss"
+    );
+}
@@ -0,0 +1,212 @@
+use chrono::{Duration, NaiveDate, Utc};
+
+use crate::common::*;
+
+/// Print the stored articles matching `query`, best matches first. `since` and `until` are
+/// compared with the publication date of the articles, or with the date they were notified when
+/// the feed doesn't tell.
+/// Words must all be found, in any order, `"quoted words"` must be found together and `word*`
+/// matches the words starting with `word`.
+pub fn search(
+    repo: &dyn SubscriptionRepository,
+    query: &str,
+    feeds: &[String],
+    since: Option<&str>,
+    until: Option<&str>,
+    limit: u32,
+) -> Result<(), GoodMorningError> {
+    info!("Searching {:?}", query);
+
+    let text = fts_query(query);
+    if text.is_empty() {
+        return Err(GoodMorningError::InvalidArgument(
+            "nothing to search".to_string(),
+        ));
+    }
+
+    let query = ArticleQuery {
+        text,
+        feeds: feeds.to_vec(),
+        since: since.map(|since| parse_day(since, 0)).transpose()?,
+        // the last day is included
+        until: until.map(|until| parse_day(until, 1)).transpose()?,
+        limit,
+    };
+
+    let results = repo.search_articles(&query);
+    if results.is_empty() {
+        println!("No article found");
+    }
+
+    for (index, result) in results.iter().enumerate() {
+        println!(
+            "{}. {} ({})",
+            index + 1,
+            result.title.as_ref().map_or("Untitled", String::as_str),
+            result.link.as_ref().map_or("no link", String::as_str)
+        );
+        println!(
+            "   {}, {}",
+            result.feed_url,
+            result.date.get(..10).unwrap_or(&result.date)
+        );
+        println!(
+            "   {}",
+            result
+                .snippet
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    Ok(())
+}
+
+// user query to fts5 syntax, where punctuation has a meaning: every word and phrase is quoted
+fn fts_query(query: &str) -> String {
+    let mut terms = Vec::new();
+
+    for (index, part) in query.split('"').enumerate() {
+        // odd parts are between quotes
+        if index % 2 == 1 {
+            if !part.trim().is_empty() {
+                terms.push(format!("\"{}\"", part.trim()));
+            }
+            continue;
+        }
+
+        for word in part.split_whitespace() {
+            let prefix = word.len() > 1 && word.ends_with('*');
+            let word = word.trim_end_matches('*');
+            if !word.is_empty() {
+                terms.push(format!("\"{}\"{}", word, if prefix { "*" } else { "" }));
+            }
+        }
+    }
+
+    terms.join(" ")
+}
+
+// `2019-03-01` or `30d` (days ago), as rfc3339 `offset` days later
+fn parse_day(day: &str, offset: i64) -> Result<String, GoodMorningError> {
+    let date = match day.strip_suffix('d').map(str::parse::<i64>) {
+        Some(Ok(days)) => (Utc::now() - Duration::days(days)).naive_utc().date(),
+        _ => NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| {
+            GoodMorningError::InvalidArgument(format!(
+                "invalid date {:?}, expected YYYY-MM-DD or a number of days like 30d",
+                day
+            ))
+        })?,
+    };
+
+    let day = date + Duration::days(offset);
+    Ok(format!("{}T00:00:00+00:00", day.format("%Y-%m-%d")))
+}
+
+#[test]
+pub fn should_quote_search_terms() {
+    assert_eq!(fts_query("rust  async"), "\"rust\" \"async\"");
+    assert_eq!(
+        fts_query("\"feed reader\" rust* c++"),
+        "\"feed reader\" \"rust\"* \"c++\""
+    );
+    assert_eq!(fts_query(" \"\" * "), "");
+
+    assert_eq!(
+        parse_day("2019-03-01", 1).unwrap(),
+        "2019-03-02T00:00:00+00:00"
+    );
+    assert!(parse_day("yesterday", 0).is_err());
+}
+
+#[test]
+pub fn should_search_stored_articles() {
+    use super::data::SQliteSubscriptionRepository;
+
+    let repo = SQliteSubscriptionRepository::new(":memory:");
+    repo.init();
+    repo.add_sub("https://blog.example.org/feed", FeedType::Rss);
+    repo.add_sub("https://news.example.org/atom", FeedType::Atom);
+    let feeds = repo.get_monitored_feeds();
+
+    let article = |title: &str, summary: &str, author: &str| Article {
+        title: Some(title.to_string()),
+        link: Some(format!("https://example.org/{}", title.len())),
+        summary: Some(summary.to_string()),
+        author: Some(author.to_string()),
+        ..Default::default()
+    };
+    let key = ArticleKey::default();
+    repo.add_article(
+        &feeds[0],
+        &article(
+            "Writing a feed reader in Rust",
+            "How the feeds are fetched and stored",
+            "Sally Ride",
+        ),
+        &key,
+    );
+    repo.add_article(
+        &feeds[1],
+        &Article {
+            pub_date: Some("Tue, 10 Jun 2003 04:00:00 GMT".to_string()),
+            ..article(
+                "Reader survey",
+                "Tell us which feed you read, and in Rust we trust",
+                "John Glenn",
+            )
+        },
+        &key,
+    );
+    repo.add_article(
+        &feeds[1],
+        &Article {
+            content: Some("A long text about a feed reader written in Rust".to_string()),
+            ..article("Launch day", "In pictures", "Sally Ride")
+        },
+        &key,
+    );
+
+    let search = |text: &str, feeds: &[&str], since: Option<&str>| {
+        repo.search_articles(&ArticleQuery {
+            text: fts_query(text),
+            feeds: feeds.iter().map(|feed| feed.to_string()).collect(),
+            since: since.map(|since| since.to_string()),
+            until: None,
+            limit: 10,
+        })
+        .into_iter()
+        .map(|result| result.title.unwrap())
+        .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+        search("\"feed reader\"", &[], None),
+        vec!["Writing a feed reader in Rust", "Launch day"]
+    );
+    assert_eq!(
+        search("rust feed", &[], None),
+        vec![
+            "Writing a feed reader in Rust",
+            "Reader survey",
+            "Launch day"
+        ]
+    );
+    assert_eq!(search("sally", &["news."], None), vec!["Launch day"]);
+    assert_eq!(search("surv*", &[], None), vec!["Reader survey"]);
+    assert!(search("rust", &[], Some("2999-01-01")).is_empty());
+    // published long before it was notified
+    assert_eq!(
+        search("rust", &[], Some("2019-01-01")),
+        vec!["Writing a feed reader in Rust", "Launch day"]
+    );
+
+    let results = repo.search_articles(&ArticleQuery {
+        text: fts_query("fetched"),
+        limit: 10,
+        ..Default::default()
+    });
+    assert_eq!(results[0].feed_url, "https://blog.example.org/feed");
+    assert_eq!(results[0].snippet, "How the feeds are [fetched] and stored");
+}
@@ -13,6 +13,7 @@ extern crate log;
 extern crate serde_derive;
 
 mod common;
+mod http;
 mod lib;
 mod notify;
 mod syndication;
@@ -36,7 +37,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         None => "./good-morning.db",
     };
 
-    let config = load_config("./good-morning.config.toml").expect("config loading failed...");
+    let mut config = load_config("./good-morning.config.toml").expect("config loading failed...");
+
+    if let Some(connect_timeout) = args.connect_timeout {
+        config.connect_timeout = connect_timeout;
+    }
+    if let Some(read_timeout) = args.read_timeout {
+        config.read_timeout = read_timeout;
+    }
+    if let Some(ref user_agent) = args.user_agent {
+        config.user_agent = user_agent.clone();
+    }
+    if let AppCommand::Run {
+        workers, deadline, ..
+    } = &args.cmd
+    {
+        config.workers = workers.unwrap_or(config.workers);
+        config.run_deadline = deadline.or(config.run_deadline);
+    }
 
     info!("using data from {:?}", data_file_path);
 
@@ -51,8 +69,56 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match &args.cmd {
         AppCommand::ListSub => lib::list_subscription(&repo),
-        AppCommand::Run { dry_run } => lib::run(&repo, *dry_run, &config).expect("run failed..."), //TODO
+        AppCommand::DedupeSubs => lib::dedupe_subscriptions(&repo),
+        AppCommand::Run { dry_run, .. } => {
+            lib::run(&repo, *dry_run, &config).expect("run failed...")
+        }
         AppCommand::Import { file_path } => lib::import_subscriptions(&repo, file_path),
+        AppCommand::AddSub {
+            url,
+            pick,
+            credential,
+            headers,
+            full_text,
+        } => {
+            let access =
+                lib::parse_access(credential, headers, &config).expect("invalid access...");
+            lib::add_subscription(&repo, url, *pick, &access, *full_text, &config)
+                .expect("subscription failed...")
+        }
+        AppCommand::SetAccess {
+            url,
+            credential,
+            headers,
+        } => {
+            let access =
+                lib::parse_access(credential, headers, &config).expect("invalid access...");
+            lib::set_access(&repo, url, &access).expect("access update failed...")
+        }
+        AppCommand::SetFullText { url, off } => lib::set_full_text(&repo, url, !*off),
+        AppCommand::Search {
+            query,
+            feeds,
+            since,
+            until,
+            limit,
+        } => lib::search(
+            &repo,
+            query,
+            feeds,
+            since.as_deref(),
+            until.as_deref(),
+            *limit,
+        )
+        .expect("search failed..."),
+        AppCommand::History { url, format } => {
+            lib::history(&repo, url, *format).expect("history failed...")
+        }
+        AppCommand::Discover { url } => lib::discover(url, &config).expect("discovery failed..."),
+        AppCommand::DownloadEnclosures { dir, max_per_feed } => {
+            lib::download_enclosures(&repo, dir, *max_per_feed, &config)
+                .expect("download failed...")
+        }
     }
 
     // println!("press enter to finish.");
@@ -79,6 +145,17 @@ struct AppCli {
     #[structopt(long = "data-path")]
     data_path: Option<std::path::PathBuf>,
 
+    /// Connect timeout of http requests, in seconds
+    #[structopt(long = "connect-timeout")]
+    connect_timeout: Option<u64>,
+
+    /// Read timeout of http requests, in seconds
+    #[structopt(long = "read-timeout")]
+    read_timeout: Option<u64>,
+
+    #[structopt(long = "user-agent")]
+    user_agent: Option<String>,
+
     #[structopt(subcommand)]
     cmd: AppCommand,
 }
@@ -95,9 +172,114 @@ enum AppCommand {
     #[structopt(name = "list-sub")]
     ListSub,
 
+    /// Merge the subscriptions to the same feed and their history
+    #[structopt(name = "dedupe-subs")]
+    DedupeSubs,
+
+    /// Subscribe to a feed, or to the feed of a web page
+    #[structopt(name = "add-sub")]
+    AddSub {
+        url: String,
+
+        /// Index of the feed to subscribe to, when the page has several
+        #[structopt(long = "pick")]
+        pick: Option<usize>,
+
+        /// Name of a credential of the config
+        #[structopt(long = "credential")]
+        credential: Option<String>,
+
+        /// Extra `Name: value` header, secrets belong in credentials
+        #[structopt(long = "header")]
+        headers: Vec<String>,
+
+        /// Download the pages of the new articles to extract their text
+        #[structopt(long = "full-text")]
+        full_text: bool,
+    },
+
+    /// Set the credential and the headers a subscription is requested with
+    #[structopt(name = "set-access")]
+    SetAccess {
+        url: String,
+
+        /// Name of a credential of the config
+        #[structopt(long = "credential")]
+        credential: Option<String>,
+
+        /// Extra `Name: value` header, secrets belong in credentials
+        #[structopt(long = "header")]
+        headers: Vec<String>,
+    },
+
+    /// Download the pages of the new articles of a subscription to extract their text
+    #[structopt(name = "set-full-text")]
+    SetFullText {
+        url: String,
+
+        /// Stop downloading them
+        #[structopt(long = "off")]
+        off: bool,
+    },
+
+    /// Search the stored articles: words, "exact phrases" and prefix* are supported
+    #[structopt(name = "search")]
+    Search {
+        query: String,
+
+        /// Part of the url of a subscription to search, can be repeated
+        #[structopt(long = "feed")]
+        feeds: Vec<String>,
+
+        /// Articles published this day (YYYY-MM-DD) or later, or in the last days (30d).
+        /// The articles without a date are filtered on the day they were notified
+        #[structopt(long = "since")]
+        since: Option<String>,
+
+        /// Articles published this day (YYYY-MM-DD) or before
+        #[structopt(long = "until")]
+        until: Option<String>,
+
+        #[structopt(long = "limit", default_value = "20")]
+        limit: u32,
+    },
+
+    /// Every stored check of a subscription, its changes and its failures
+    #[structopt(name = "history")]
+    History {
+        url: String,
+
+        /// table or json
+        #[structopt(long = "format", default_value = "table")]
+        format: lib::HistoryFormat,
+    },
+
+    /// List the feeds of a web page
+    #[structopt(name = "discover")]
+    Discover { url: String },
+
     #[structopt(name = "run")]
     Run {
+        /// Check the feeds and print the report, without storing, archiving nor sending anything
         #[structopt(long = "dry-run")]
         dry_run: bool,
+
+        /// Number of feeds checked at the same time
+        #[structopt(long = "workers")]
+        workers: Option<usize>,
+
+        /// Seconds after which the run stops checking feeds
+        #[structopt(long = "deadline")]
+        deadline: Option<u64>,
+    },
+
+    #[structopt(name = "download-enclosures")]
+    DownloadEnclosures {
+        #[structopt(short = "d", long = "dir")]
+        dir: std::path::PathBuf,
+
+        /// Number of the most recent articles with enclosures of each feed, notified or not
+        #[structopt(long = "max-per-feed", default_value = "3")]
+        max_per_feed: usize,
     },
 }
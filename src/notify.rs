@@ -1,18 +1,48 @@
 mod sendgrid;
 
 use crate::common::*;
+use reqwest::Client;
 use sendgrid::{send_mail, MailRequest};
 use std::fmt::Write;
+use url::Url;
 
 //TODO: make types to handle config
 pub fn notify_updates(
     config: &Config,
-    updates: Vec<(MonitoredFeed, FeedUpdateKind)>,
+    client: &Client,
+    updates: &[FeedUpdate],
 ) -> Result<(), GoodMorningError> {
+    let content = report(updates);
+
+    if !content.is_empty() {
+        match &config.report_type {
+            ReportType::Email => {
+                let to = &config.mail_to;
+                let mail_request =
+                    MailRequest::new("New blog posts", to, "good-morning@chartios.com", &content);
+                send_mail(client, &config.sendgrid_token, &mail_request)?;
+            }
+            ReportType::Stdout => println!("{}", content),
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the report on stdout, whatever the report type
+pub fn print_updates(updates: &[FeedUpdate]) {
+    let content = report(updates);
+    if !content.is_empty() {
+        println!("{}", content);
+    }
+}
+
+fn report(updates: &[FeedUpdate]) -> String {
     let mut content: String = String::new();
 
-    for (feed, update_kind) in updates {
-        match update_kind {
+    for update in updates {
+        let feed = &update.feed;
+        match update.kind {
             FeedUpdateKind::NewArticle => {
                 writeln!(content, "NewArticle at {}", feed.url).expect("Formatting error")
             }
@@ -25,20 +55,116 @@ pub fn notify_updates(
             FeedUpdateKind::Title => {
                 writeln!(content, "Title updated for {}", feed.url).expect("Formatting error")
             }
+            FeedUpdateKind::Retired => writeln!(
+                content,
+                "Retired {}: it {}, it won't be checked anymore",
+                feed.url,
+                feed.retired.as_ref().map_or("is gone", String::as_str)
+            )
+            .expect("Formatting error"),
+        }
+
+        for article in update.new_articles.iter() {
+            write_article(&mut content, article).expect("Formatting error");
+        }
+
+        for (rule, count) in update.suppressed.iter() {
+            writeln!(content, "    ({} filtered out: {})", count, rule).expect("Formatting error");
         }
     }
 
-    if !content.is_empty() {
-        match &config.report_type {
-            ReportType::Email => {
-                let to = &config.mail_to;
-                let mail_request =
-                    MailRequest::new("New blog posts", to, "good-morning@chartios.com", &content);
-                send_mail(&config.sendgrid_token, &mail_request)?;
-            }
-            ReportType::Stdout => println!("{}", content),
+    content
+}
+
+fn write_article(content: &mut String, article: &Article) -> std::fmt::Result {
+    writeln!(
+        content,
+        "    - {} ({})",
+        article.title.as_ref().map_or("Untitled", String::as_str),
+        article.link.as_ref().map_or("no link", String::as_str)
+    )?;
+
+    if let Some(text) = article.content.as_ref().or(article.summary.as_ref()) {
+        let snippet = snippet(text);
+        if !snippet.is_empty() {
+            writeln!(content, "      {}", snippet)?;
+        }
+    }
+
+    if let Some(ref thumbnail) = article.thumbnail {
+        writeln!(content, "      thumbnail: {}", thumbnail)?;
+    }
+
+    for enclosure in article.enclosures.iter() {
+        let mut details = vec![enclosure
+            .mime_type
+            .clone()
+            .unwrap_or_else(|| "unknown type".to_string())];
+
+        if let Some(duration) = enclosure.duration {
+            details.push(format_duration(duration));
         }
+        if let Some(length) = enclosure.length {
+            details.push(format_size(length));
+        }
+
+        writeln!(content, "      {} ({})", details.join(", "), enclosure.url)?;
+    }
+
+    if let Some(ref path) = article.archive_path {
+        match Url::from_file_path(path) {
+            Ok(url) => writeln!(content, "      archived: {}", url)?,
+            Err(()) => writeln!(content, "      archived: {}", path.display())?,
+        }
+    }
+
+    if !article.also_in.is_empty() {
+        writeln!(content, "      also in: {}", article.also_in.join(", "))?;
     }
 
     Ok(())
 }
+
+// first words of the text, on a single line
+fn snippet(text: &str) -> String {
+    const MAX_CHARS: usize = 200;
+
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.chars().count() <= MAX_CHARS {
+        return text;
+    }
+
+    let mut snippet: String = text.chars().take(MAX_CHARS).collect();
+    if let Some(end) = snippet.rfind(' ') {
+        snippet.truncate(end);
+    }
+    snippet.push('…');
+    snippet
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
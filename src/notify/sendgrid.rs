@@ -46,10 +46,14 @@ impl MailRequest {
     }
 }
 
-pub fn send_mail(api_token: &str, mail_request: &MailRequest) -> Result<(), GoodMorningError> {
+pub fn send_mail(
+    client: &Client,
+    api_token: &str,
+    mail_request: &MailRequest,
+) -> Result<(), GoodMorningError> {
     let request_url = "https://api.sendgrid.com/v3/mail/send".to_string();
 
-    let mut response = Client::new()
+    let mut response = client
         .post(&request_url)
         .bearer_auth(api_token)
         .json(mail_request)
@@ -1,51 +1,375 @@
 mod atom;
+mod compression;
+mod date;
+mod discovery;
+mod encoding;
+mod html;
+mod link;
+mod media;
+mod readability;
 mod rss;
+mod single_file;
+mod text;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use chrono::Utc;
-use reqwest;
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use url::Url;
 
 use crate::common::*;
-use atom::parse_atom_feed;
-use rss::parse_rss_feed;
+use crate::http::{
+    authorize, build_client, is_transient, parse_retry_after, read_limited, HostThrottle,
+    RetryPolicy,
+};
+use atom::{parse_atom_feed, Entry};
+use compression::{decompress, ACCEPTED_ENCODINGS};
+use date::sort_by_date;
+use encoding::decode_body;
+use media::Media;
+use readability::extract_article_text;
+use rss::{parse_rss_feed, Item};
+use single_file::inline_images;
+use text::html_to_text;
+
+pub use date::parse_date;
+pub use discovery::{discover_feeds, FeedCandidate};
+pub use link::{clean_url, is_feedburner_redirect, strip_tracking_params, url_key};
+
+// longer delays asked by a host are waited for until the next run
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// What the feed checks of a command share, cheap to clone for every worker
+#[derive(Clone)]
+pub struct CheckContext {
+    pub client: Client,
+    pub parse_mode: ParseMode,
+    pub hosts: Arc<HostThrottle>,
+    pub retry: RetryPolicy,
+    pub credentials: Arc<HashMap<String, Credential>>,
+    pub max_body_size: u64,
+}
+
+impl CheckContext {
+    pub fn new(config: &Config) -> Result<CheckContext, GoodMorningError> {
+        Ok(CheckContext {
+            client: build_client(config)?,
+            parse_mode: config.parse_mode,
+            hosts: Arc::new(HostThrottle::new(Duration::from_millis(config.host_delay))),
+            retry: RetryPolicy::new(config),
+            credentials: Arc::new(config.credentials.clone()),
+            max_body_size: config.max_body_size,
+        })
+    }
+}
 
-pub fn check_feed(feed: &MonitoredFeed) -> Option<FeedCheckResult> {
+pub fn check_feed(
+    context: &CheckContext,
+    feed: &MonitoredFeed,
+) -> Result<FeedCheckResult, GoodMorningError> {
     debug!("Checking {:?}", feed.url);
 
-    let mut res = match reqwest::get(&feed.url) {
-        Ok(req_result) => req_result,
+    let document = match fetch(context, &feed.url, &feed.access) {
+        Ok(document) => document,
+        Err(GoodMorningError::HostBackOff(host)) => {
+            info!(
+                "Deferring {:?} to the next run, {} asked us to back off",
+                feed.url, host
+            );
+            return Err(GoodMorningError::HostBackOff(host));
+        }
         Err(err) => {
             warn!("Error happened while requesting {:?} ({:?})", feed.url, err);
-            return None;
+            return Err(err);
         }
     };
 
-    let body = match res.text() {
-        Ok(b) => b,
-        Err(err) => {
-            warn!(
-                "Error happened while opening body of {:?} ({:?})",
-                feed.url, err
-            );
-            return None;
+    // links are relative to the final url, after redirections
+    let base = &document.url;
+
+    let check_result = match feed.kind {
+        FeedType::Rss => check_rss(feed, &document.body, base, context.parse_mode),
+        FeedType::Atom => check_atom(feed, &document.body, base),
+    };
+
+    check_result.ok_or(GoodMorningError::Parse)
+}
+
+/// Fetched document, decoded to utf-8
+struct Document {
+    body: String,
+    /// final url, after redirections
+    url: Url,
+}
+
+/// Fetch a document over http, or read it when `url` is a `file://` url or a plain path.
+fn fetch(
+    context: &CheckContext,
+    url: &str,
+    access: &FeedAccess,
+) -> Result<Document, GoodMorningError> {
+    match local_path(url) {
+        Some(path) => fetch_file(&path, context.max_body_size),
+        None => fetch_http(context, url, access),
+    }
+}
+
+fn local_path(url: &str) -> Option<PathBuf> {
+    match Url::parse(url) {
+        Ok(ref parsed) if parsed.scheme() == "file" => parsed.to_file_path().ok(),
+        // windows drive letters look like schemes
+        Ok(ref parsed) if parsed.scheme().len() == 1 => Some(PathBuf::from(url)),
+        Ok(_) => None,
+        Err(_) => Some(PathBuf::from(url)),
+    }
+}
+
+fn fetch_file(path: &Path, max_body_size: u64) -> Result<Document, GoodMorningError> {
+    debug!("Reading {:?}", path);
+
+    let bytes = read_limited(File::open(path)?, max_body_size)?;
+    let bytes = decompress(bytes, None, max_body_size)?;
+    let url = Url::from_file_path(fs::canonicalize(path)?).map_err(|_| GoodMorningError::Parse)?;
+
+    Ok(Document {
+        body: decode_body(&bytes, None),
+        url,
+    })
+}
+
+fn fetch_http(
+    context: &CheckContext,
+    url: &str,
+    access: &FeedAccess,
+) -> Result<Document, GoodMorningError> {
+    let attempts = context.retry.retries + 1;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        debug!("Requesting {:?} (attempt {}/{})", url, attempt, attempts);
+
+        match fetch_http_once(context, url, access) {
+            // the host is paused until then
+            Err(GoodMorningError::RetryAfter(_, delay)) if attempt < attempts => {
+                warn!(
+                    "Attempt {}/{} on {:?} throttled, retrying in {:?}",
+                    attempt, attempts, url, delay
+                );
+            }
+            Err(GoodMorningError::RetryAfter(host, _)) => {
+                context.hosts.back_off(&host);
+                return Err(GoodMorningError::HostBackOff(host));
+            }
+            Err(ref err) if attempt < attempts && is_transient(err) => {
+                let delay = context.retry.delay(attempt);
+                warn!(
+                    "Attempt {}/{} on {:?} failed ({:?}), retrying in {:?}",
+                    attempt, attempts, url, err, delay
+                );
+                thread::sleep(delay);
+            }
+            Err(err) => {
+                warn!("Attempt {}/{} on {:?} failed", attempt, attempts, url);
+                return Err(err);
+            }
+            Ok(document) => return Ok(document),
         }
+    }
+}
+
+fn fetch_http_once(
+    context: &CheckContext,
+    url: &str,
+    access: &FeedAccess,
+) -> Result<Document, GoodMorningError> {
+    let host = host_of(url);
+
+    let _permit = context.hosts.acquire(&host)?;
+    let request = context
+        .client
+        .get(url)
+        .header(ACCEPT_ENCODING, ACCEPTED_ENCODINGS);
+    let res = authorize(request, access, &context.credentials)?.send()?;
+
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let backs_off = res.status() == StatusCode::TOO_MANY_REQUESTS
+        || (res.status() == StatusCode::SERVICE_UNAVAILABLE && retry_after.is_some());
+
+    if backs_off {
+        match retry_after {
+            Some(delay) if delay <= MAX_RETRY_AFTER => {
+                context.hosts.pause(&host, delay);
+                return Err(GoodMorningError::RetryAfter(host, delay));
+            }
+            _ => {
+                warn!(
+                    "{} asked us to back off (retry after {:?})",
+                    host, retry_after
+                );
+                context.hosts.back_off(&host);
+                return Err(GoodMorningError::HostBackOff(host));
+            }
+        }
+    }
+
+    let mut res = res.error_for_status()?;
+
+    if res
+        .content_length()
+        .is_some_and(|length| length > context.max_body_size)
+    {
+        return Err(GoodMorningError::BodyTooLarge(context.max_body_size));
+    }
+
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
     };
+    let content_type = header(CONTENT_TYPE);
+    let content_encoding = header(CONTENT_ENCODING);
 
-    match feed.kind {
-        FeedType::Rss => check_rss(feed, body.as_str()),
-        FeedType::Atom => check_atom(feed, body.as_str()),
+    let bytes = read_limited(&mut res, context.max_body_size)?;
+    let bytes = decompress(bytes, content_encoding.as_deref(), context.max_body_size)?;
+
+    Ok(Document {
+        body: decode_body(&bytes, content_type.as_deref()),
+        url: res.url().clone(),
+    })
+}
+
+/// Download the page of an article and extract its main text.
+/// `None` when no part of the page looks like the text of an article.
+pub fn fetch_full_text(
+    context: &CheckContext,
+    url: &str,
+) -> Result<Option<String>, GoodMorningError> {
+    let document = fetch_http(context, web_url(url)?.as_str(), &FeedAccess::default())?;
+    Ok(extract_article_text(&document.body))
+}
+
+// links found in feeds and pages are not trusted to read local files, unlike subscriptions
+fn web_url(url: &str) -> Result<Url, GoodMorningError> {
+    match Url::parse(url.trim()) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => Ok(parsed),
+        _ => Err(GoodMorningError::UnsupportedUrl(url.to_string())),
     }
 }
 
-fn check_rss(feed: &MonitoredFeed, body: &str) -> Option<FeedCheckResult> {
+/// Download the page of an article as a single html file, its images inlined.
+pub fn fetch_single_file_page(
+    context: &CheckContext,
+    url: &str,
+) -> Result<String, GoodMorningError> {
+    let document = fetch_http(context, web_url(url)?.as_str(), &FeedAccess::default())?;
+
+    Ok(inline_images(
+        &document.body,
+        &document.url,
+        |image| match fetch_data_uri(context, image) {
+            Ok(data_uri) => Some(data_uri),
+            Err(err) => {
+                warn!("Could not inline {:?} ({:?})", image, err);
+                None
+            }
+        },
+    ))
+}
+
+// the resource as a `data:` url
+fn fetch_data_uri(context: &CheckContext, url: &str) -> Result<String, GoodMorningError> {
+    let url = web_url(url)?;
+    let _permit = context.hosts.acquire(&host_of(url.as_str()))?;
+    let mut res = context.client.get(url.clone()).send()?.error_for_status()?;
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or("").trim().to_string());
+    let bytes = read_limited(&mut res, context.max_body_size)?;
+
+    let mime_type = content_type
+        .filter(|mime_type| mime_type.starts_with("image/"))
+        .unwrap_or_else(|| image_type(url.as_str()).to_string());
+
+    Ok(format!(
+        "data:{};base64,{}",
+        mime_type,
+        base64::encode(&bytes)
+    ))
+}
+
+// guessed from the extension, for servers and files not telling it
+fn image_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Host requests to `url` are throttled on
+pub fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default()
+}
+
+/// Where `url` ends up after its redirections, its content isn't downloaded.
+pub fn resolve_redirect(context: &CheckContext, url: &str) -> Result<String, GoodMorningError> {
+    let host = host_of(url);
+
+    let _permit = context.hosts.acquire(&host)?;
+    let res = context.client.head(url).send()?.error_for_status()?;
+
+    Ok(res.url().to_string())
+}
+
+fn check_rss(
+    feed: &MonitoredFeed,
+    body: &str,
+    base: &Url,
+    parse_mode: ParseMode,
+) -> Option<FeedCheckResult> {
     debug!("Parsing rss feed {:?}", feed.url);
 
-    let feed = match parse_rss_feed(body).ok() {
-        None => return None,
-        Some(f) => f,
+    let url = &feed.url;
+    let mut feed = match parse_rss_feed(body.as_bytes(), Some(base), parse_mode) {
+        Ok(f) => f,
+        Err(err) => {
+            warn!("Error happened while parsing {:?} ({})", url, err);
+            return None;
+        }
     };
 
-    let channel = feed.channels.first().unwrap(); //TODO ...
-    let last_article = channel.items.first(); //TODO ...
+    for warning in feed.warnings.iter() {
+        warn!("Issue found in {:?}: {}", url, warning);
+    }
+
+    // never empty when parsed, a feed without channel would be a parse error anyway
+    let channel = feed.channels.first_mut()?;
+    sort_by_date(&mut channel.items, |item| item.pub_date.as_deref());
+    let last_article = channel.items.first();
 
     Some(FeedCheckResult {
         check_date: Utc::now().to_rfc3339(),
@@ -55,26 +379,329 @@ fn check_rss(feed: &MonitoredFeed, body: &str) -> Option<FeedCheckResult> {
         last_article_guid: last_article.and_then(|art| art.guid.clone()),
         last_article_pub_date: last_article.and_then(|art| art.pub_date.clone()),
         last_article_hash: Some("None".to_string()),
+        articles: channel.items.iter().map(rss_article).collect(),
     })
 }
 
-fn check_atom(feed: &MonitoredFeed, body: &str) -> Option<FeedCheckResult> {
+fn check_atom(feed: &MonitoredFeed, body: &str, base: &Url) -> Option<FeedCheckResult> {
     debug!("Parsing atom feed {:?}", feed.url);
 
-    let feed = match parse_atom_feed(body).ok() {
+    let mut feed = match parse_atom_feed(body.as_bytes(), Some(base)).ok() {
         None => return None,
         Some(f) => f,
     };
 
-    let last_article = feed.entries.first(); //TODO ...
+    sort_by_date(&mut feed.entries, |entry| Some(entry_date(entry)));
+    let last_article = feed.entries.first();
 
     Some(FeedCheckResult {
         check_date: Utc::now().to_rfc3339(),
-        title: feed.title.clone(),
+        title: feed.title.text(),
         pub_date: Some(feed.updated.clone()),
-        last_article_title: last_article.map(|art| art.title.clone()),
+        last_article_title: last_article.map(|art| art.title.text()),
         last_article_guid: last_article.map(|art| art.guid.clone()),
-        last_article_pub_date: last_article.map(|art| art.updated.clone()),
+        last_article_pub_date: last_article.map(|art| entry_date(art).to_string()),
         last_article_hash: Some("None".to_string()),
+        articles: feed.entries.iter().map(atom_article).collect(),
     })
 }
+
+// an edit doesn't make an entry newer
+fn entry_date(entry: &Entry) -> &str {
+    entry.published.as_ref().unwrap_or(&entry.updated)
+}
+
+fn rss_article(item: &Item) -> Article {
+    Article {
+        guid: item.guid.clone(),
+        title: item.title.clone(),
+        link: item.link.clone(),
+        pub_date: item.pub_date.clone(),
+        enclosures: media_enclosures(&item.media, Vec::new()),
+        thumbnail: item.media.thumbnail.clone(),
+        summary: item.description.as_deref().map(html_to_text),
+        author: item.author.clone(),
+        categories: item.categories.clone(),
+        content: None,
+        archive_path: None,
+        also_in: Vec::new(),
+    }
+}
+
+fn atom_article(entry: &Entry) -> Article {
+    let links = entry
+        .enclosures
+        .iter()
+        .map(|link| Enclosure {
+            url: link.href.clone(),
+            mime_type: link.mime_type.clone(),
+            length: link.length,
+            duration: None,
+        })
+        .collect();
+
+    Article {
+        guid: Some(entry.guid.clone()),
+        title: Some(entry.title.text()),
+        link: Some(entry.link.clone()),
+        pub_date: Some(entry_date(entry).to_string()),
+        enclosures: media_enclosures(&entry.media, links),
+        thumbnail: entry.media.thumbnail.clone(),
+        summary: entry
+            .summary
+            .as_ref()
+            .or(entry.content.as_ref())
+            .map(|text| text.text()),
+        author: entry.author.clone(),
+        categories: entry.categories.clone(),
+        content: None,
+        archive_path: None,
+        also_in: Vec::new(),
+    }
+}
+
+// the itunes duration is about the enclosures of the item
+fn media_enclosures(media: &Media, mut enclosures: Vec<Enclosure>) -> Vec<Enclosure> {
+    enclosures.extend(media.enclosures.iter().cloned());
+
+    for enclosure in enclosures.iter_mut() {
+        if enclosure.duration.is_none() {
+            enclosure.duration = media.duration;
+        }
+    }
+
+    enclosures
+}
+
+#[test]
+pub fn should_pick_newest_rss_item_whatever_the_document_order() {
+    let feed = MonitoredFeed {
+        id: 1,
+        url: "http://liftoff.msfc.nasa.gov/rss.xml".to_string(),
+        kind: FeedType::Rss,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let rss_sample = r#"
+        <?xml version="1.0"?>
+        <rss version="2.0">
+            <channel>
+                <title>Liftoff News</title>
+                <link>http://liftoff.msfc.nasa.gov/</link>
+                <item>
+                    <title>Astronauts' Dirty Laundry</title>
+                    <pubDate>Tue, 20 May 2003 08:56:02 GMT</pubDate>
+                    <guid>http://liftoff.msfc.nasa.gov/2003/05/20.html#item570</guid>
+                </item>
+                <item>
+                    <title>The Engine That Does More</title>
+                    <pubDate>Tue, 27 May 2003 08:37:32 GMT</pubDate>
+                    <guid>http://liftoff.msfc.nasa.gov/2003/05/27.html#item571</guid>
+                </item>
+            </channel>
+        </rss>
+    "#;
+
+    let base = Url::parse(&feed.url).unwrap();
+    let check = check_rss(&feed, rss_sample, &base, ParseMode::Strict).unwrap();
+
+    assert_eq!(
+        check.last_article_title,
+        Some("The Engine That Does More".to_string())
+    );
+    assert_eq!(
+        check.last_article_guid,
+        Some("http://liftoff.msfc.nasa.gov/2003/05/27.html#item571".to_string())
+    );
+}
+
+#[test]
+pub fn should_prefer_atom_published_date_to_updated_date() {
+    let feed = MonitoredFeed {
+        id: 1,
+        url: "http://example.org/feed.atom".to_string(),
+        kind: FeedType::Atom,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <title>Example Feed</title>
+            <link href="http://example.org/"/>
+            <updated>2003-12-20T18:30:02Z</updated>
+            <entry>
+                <title>Old post, fixed typo</title>
+                <link href="http://example.org/2003/12/01/old"/>
+                <id>urn:uuid:old</id>
+                <published>2003-12-01T10:00:00Z</published>
+                <updated>2003-12-20T18:30:02Z</updated>
+            </entry>
+            <entry>
+                <title>New post</title>
+                <link href="http://example.org/2003/12/13/new"/>
+                <id>urn:uuid:new</id>
+                <published>2003-12-13T18:30:02Z</published>
+                <updated>2003-12-14T08:00:00Z</updated>
+            </entry>
+        </feed>
+    "#;
+
+    let base = Url::parse(&feed.url).unwrap();
+    let check = check_atom(&feed, atom_sample, &base).unwrap();
+
+    assert_eq!(check.last_article_title, Some("New post".to_string()));
+    assert_eq!(check.last_article_guid, Some("urn:uuid:new".to_string()));
+    // compared with the dates of the articles on the next check
+    assert_eq!(
+        check.last_article_pub_date,
+        Some("2003-12-13T18:30:02Z".to_string())
+    );
+    assert_eq!(
+        check.articles[1].pub_date,
+        Some("2003-12-01T10:00:00Z".to_string())
+    );
+}
+
+#[test]
+pub fn should_gather_atom_enclosures() {
+    let feed = MonitoredFeed {
+        id: 1,
+        url: "http://example.org/feed.atom".to_string(),
+        kind: FeedType::Atom,
+        last_check: None,
+        retired: None,
+        not_found_streak: 0,
+        access: FeedAccess::default(),
+        full_text: false,
+    };
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">
+            <title>Example Feed</title>
+            <link href="http://example.org/"/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title>Episode 1</title>
+                <link href="http://example.org/episodes/1"/>
+                <link rel="enclosure" type="audio/mpeg" length="1337" href="/episodes/1.mp3"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+                <media:group>
+                    <media:thumbnail url="http://example.org/episodes/1.jpg" width="480"/>
+                </media:group>
+                <itunes:duration>30:00</itunes:duration>
+            </entry>
+        </feed>
+    "#;
+
+    let base = Url::parse(&feed.url).unwrap();
+    let check = check_atom(&feed, atom_sample, &base).unwrap();
+    let article = check.articles.first().unwrap();
+
+    assert_eq!(
+        article.enclosures,
+        vec![Enclosure {
+            url: "http://example.org/episodes/1.mp3".to_string(),
+            mime_type: Some("audio/mpeg".to_string()),
+            length: Some(1337),
+            duration: Some(1800),
+        }]
+    );
+    assert_eq!(
+        article.thumbnail,
+        Some("http://example.org/episodes/1.jpg".to_string())
+    );
+}
+
+#[test]
+pub fn should_read_local_files() {
+    assert_eq!(local_path("http://example.org/feed.xml"), None);
+    assert_eq!(
+        local_path("file:///var/feeds/build.xml"),
+        Some(PathBuf::from("/var/feeds/build.xml"))
+    );
+    assert_eq!(
+        local_path("feeds/build.xml"),
+        Some(PathBuf::from("feeds/build.xml"))
+    );
+    assert_eq!(
+        local_path("C:\\feeds\\build.xml"),
+        Some(PathBuf::from("C:\\feeds\\build.xml"))
+    );
+
+    let context = CheckContext {
+        client: Client::new(),
+        parse_mode: ParseMode::Strict,
+        hosts: Arc::new(HostThrottle::new(Duration::from_secs(0))),
+        retry: RetryPolicy {
+            retries: 0,
+            base_delay: Duration::from_secs(0),
+        },
+        credentials: Arc::new(HashMap::new()),
+        max_body_size: 1024 * 1024,
+    };
+    let access = FeedAccess::default();
+    let document = fetch(&context, "fixtures/feeds/liftoff.rss", &access).unwrap();
+    assert_eq!(document.url.scheme(), "file");
+    assert!(document.body.contains("<title>Liftoff News</title>"));
+
+    let document = fetch(&context, "fixtures/feeds/liftoff.rss.gz", &access).unwrap();
+    assert!(document.body.contains("<title>Liftoff News</title>"));
+
+    let small = CheckContext {
+        max_body_size: 64,
+        ..context.clone()
+    };
+    assert!(fetch(&small, "fixtures/feeds/liftoff.rss", &access).is_err());
+
+    assert!(fetch(&context, "fixtures/feeds/missing.rss", &access).is_err());
+}
+
+#[test]
+pub fn should_only_fetch_web_article_pages() {
+    let context = CheckContext {
+        client: Client::new(),
+        parse_mode: ParseMode::Strict,
+        hosts: Arc::new(HostThrottle::new(Duration::from_secs(0))),
+        retry: RetryPolicy {
+            retries: 0,
+            base_delay: Duration::from_secs(0),
+        },
+        credentials: Arc::new(HashMap::new()),
+        max_body_size: 1024 * 1024,
+    };
+
+    for link in &[
+        "file:///etc/passwd",
+        "fixtures/pages/blog-post.html",
+        "C:\\Windows\\win.ini",
+        "ftp://example.org/post.html",
+    ] {
+        match fetch_full_text(&context, link) {
+            Err(GoodMorningError::UnsupportedUrl(url)) => assert_eq!(url, *link),
+            other => panic!("{} fetched: {:?}", link, other),
+        }
+        assert!(matches!(
+            fetch_single_file_page(&context, link),
+            Err(GoodMorningError::UnsupportedUrl(_))
+        ));
+        // images keep their live url
+        assert!(matches!(
+            fetch_data_uri(&context, link),
+            Err(GoodMorningError::UnsupportedUrl(_))
+        ));
+    }
+
+    assert_eq!(image_type("https://example.org/a.JPG?w=200"), "image/jpeg");
+    assert_eq!(
+        image_type("https://example.org/pixel"),
+        "application/octet-stream"
+    );
+}
@@ -1,11 +1,17 @@
+use std::io::BufRead;
+
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
+use url::Url;
 
+use super::link::{resolve_link, xml_base};
+use super::media::{attr, parse_duration, Media};
+use super::text::{parse_text_construct, TextConstruct};
 use crate::common::GoodMorningError;
 
 #[derive(Debug)]
 pub struct Feed {
-    pub title: String,
+    pub title: TextConstruct,
     pub link: String,
     pub updated: String,
     pub entries: Vec<Entry>,
@@ -13,28 +19,112 @@ pub struct Feed {
 
 #[derive(Debug)]
 pub struct Entry {
-    pub title: String,
+    pub title: TextConstruct,
     pub link: String,
     pub guid: String,
     pub updated: String,
+    pub published: Option<String>,
+    pub summary: Option<TextConstruct>,
+    pub content: Option<TextConstruct>,
+    pub enclosures: Vec<Link>,
+    pub author: Option<String>,
+    pub categories: Vec<String>,
+    pub media: Media,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub href: String,
+    pub mime_type: Option<String>,
+    pub title: Option<String>,
+    pub length: Option<u64>,
+}
+
+// Links of a feed or an entry, sorted by relation
+#[derive(Debug, Default)]
+struct Links {
+    alternate: Option<Link>,
+    enclosures: Vec<Link>,
+    related: Vec<Link>,
+    via: Option<Link>,
+}
+
+impl Links {
+    fn push(&mut self, rel: &str, link: Link) {
+        // registered relations may also be written as full IRIs
+        let rel = rel
+            .trim()
+            .trim_start_matches("http://www.iana.org/assignments/relation/");
+
+        match rel {
+            "" | "alternate" => {
+                if self.alternate.is_none() {
+                    self.alternate = Some(link)
+                }
+            }
+            // the feed document, not a page to read
+            "self" => (),
+            "enclosure" => self.enclosures.push(link),
+            "related" => self.related.push(link),
+            "via" => self.via = Some(link),
+            _ => debug!("Ignoring link {:?} with rel {:?}", link.href, rel),
+        }
+    }
+
+    /// The page to read: the alternate link, or else the page this one comes from or a related one
+    fn alternate_href(&self) -> String {
+        self.alternate
+            .as_ref()
+            .or(self.via.as_ref())
+            .or(self.related.first())
+            .map_or("".to_string(), |link| link.href.clone())
+    }
 }
 
-fn parse_entry<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Entry, GoodMorningError> {
+fn parse_entry<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+    base: Option<&Url>,
+) -> Result<Entry, GoodMorningError> {
     let mut buf = Vec::new();
 
-    let mut title: String = "".to_string();
-    let mut link: String = "".to_string();
+    let mut title = TextConstruct::default();
+    let mut links = Links::default();
     let mut guid: String = "".to_string();
     let mut updated: String = "".to_string();
+    let mut published: Option<String> = None;
+    let mut summary: Option<TextConstruct> = None;
+    let mut content: Option<TextConstruct> = None;
+    let mut author: Option<String> = None;
+    let mut categories = Vec::new();
+    let mut media = Media::default();
 
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"title" => title = reader.read_text(b"title", &mut buf)?,
-                b"link" => link = extract_attr(b"href", &e, reader)?,
+                b"title" => title = parse_text_construct(e, reader)?.unwrap_or_default(),
+                b"summary" => summary = parse_text_construct(e, reader)?,
+                b"content" => content = parse_text_construct(e, reader)?,
+                b"link" => {
+                    let (rel, link) = parse_link(e, reader, base)?;
+                    links.push(&rel, link)
+                }
+                // metadata of the original feed, its links are not ours
+                b"source" => reader.read_to_end(b"source", &mut buf)?,
+                b"author" => author = parse_author(reader)?,
+                b"category" => {
+                    categories.extend(attr(e, reader, b"term")?);
+                    reader.read_to_end(b"category", &mut buf)?
+                }
                 b"id" => guid = reader.read_text(b"id", &mut buf)?,
                 b"updated" => updated = reader.read_text(b"updated", &mut buf)?,
-                _ => (),
+                b"published" => published = Some(reader.read_text(b"published", &mut buf)?),
+                b"itunes:duration" => {
+                    media.duration =
+                        parse_duration(&reader.read_text(b"itunes:duration", &mut buf)?)
+                }
+                _ => {
+                    media.read_element(e, reader, base)?;
+                }
             },
             Ok(Event::End(ref e)) => {
                 if let b"entry" = e.name() {
@@ -50,6 +140,8 @@ fn parse_entry<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Entry, Goo
         buf.clear();
     }
 
+    let link = links.alternate_href();
+
     if title.is_empty() || link.is_empty() || updated.is_empty() || guid.is_empty() {
         return Err(GoodMorningError::MissingFeedInfo);
     }
@@ -59,32 +151,75 @@ fn parse_entry<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Entry, Goo
         link,
         guid,
         updated,
+        published,
+        summary,
+        content,
+        enclosures: links.enclosures,
+        author,
+        categories,
+        media,
     })
 }
 
-pub fn parse_atom_feed(xml: &str) -> Result<Feed, GoodMorningError> {
-    if xml.is_empty() {
+/// Name of the author, the other person constructs are ignored
+fn parse_author<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+) -> Result<Option<String>, GoodMorningError> {
+    let mut buf = Vec::new();
+    let mut name = None;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.name() == b"name" => {
+                name = Some(reader.read_text(b"name", &mut buf)?)
+            }
+            Ok(Event::End(ref e)) if e.name() == b"author" => break,
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(GoodMorningError::XmlParse(e)),
+            _ => (),
+        }
+
+        buf.clear();
+    }
+
+    Ok(name)
+}
+
+/// Parse an atom document, relative links are resolved against `base` (the document url).
+pub fn parse_atom_feed<B: BufRead>(
+    mut input: B,
+    base: Option<&Url>,
+) -> Result<Feed, GoodMorningError> {
+    if input.fill_buf()?.is_empty() {
         return Err(GoodMorningError::Parse);
     }
 
-    let mut reader = Reader::from_str(xml);
+    let mut reader = Reader::from_reader(input);
     reader.trim_text(true).expand_empty_elements(true);
 
     let mut entries = Vec::new();
 
     let mut buf = Vec::new();
 
-    let mut title: String = "".to_string();
-    let mut link: String = "".to_string();
+    let mut title = TextConstruct::default();
+    let mut links = Links::default();
     let mut updated: String = "".to_string();
+    let mut base = base.cloned();
 
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"title" => title = reader.read_text(b"title", &mut buf)?,
+                b"feed" => base = xml_base(e, &reader, base.as_ref())?,
+                b"title" => title = parse_text_construct(e, &mut reader)?.unwrap_or_default(),
                 b"updated" => updated = reader.read_text(b"updated", &mut buf)?,
-                b"link" => link = extract_attr(b"href", &e, &mut reader)?,
-                b"entry" => entries.push(parse_entry(&mut reader)?),
+                b"link" => {
+                    let (rel, link) = parse_link(e, &reader, base.as_ref())?;
+                    links.push(&rel, link)
+                }
+                b"entry" => {
+                    let entry_base = xml_base(e, &reader, base.as_ref())?;
+                    entries.push(parse_entry(&mut reader, entry_base.as_ref())?)
+                }
                 _ => (),
             },
             Ok(Event::Eof) => break,
@@ -96,6 +231,8 @@ pub fn parse_atom_feed(xml: &str) -> Result<Feed, GoodMorningError> {
         buf.clear();
     }
 
+    let link = links.alternate_href();
+
     if title.is_empty() || link.is_empty() || updated.is_empty() || entries.is_empty() {
         return Err(GoodMorningError::MissingFeedInfo);
     }
@@ -108,18 +245,41 @@ pub fn parse_atom_feed(xml: &str) -> Result<Feed, GoodMorningError> {
     })
 }
 
-fn extract_attr<B: std::io::BufRead>(
-    name: &[u8],
+/// Read a link element, returns its relation and the link itself.
+fn parse_link<B: std::io::BufRead>(
     event: &BytesStart,
-    reader: &mut Reader<B>,
-) -> Result<String, GoodMorningError> {
+    reader: &Reader<B>,
+    base: Option<&Url>,
+) -> Result<(String, Link), GoodMorningError> {
+    let mut href = None;
+    let mut rel = "".to_string();
+    let mut mime_type = None;
+    let mut title = None;
+    let mut length = None;
+
     for attr in event.attributes() {
         let attr = attr?;
-        if attr.key == name {
-            return Ok(attr.unescape_and_decode_value(reader)?);
+        match attr.key {
+            b"href" => href = Some(attr.unescape_and_decode_value(reader)?),
+            b"rel" => rel = attr.unescape_and_decode_value(reader)?,
+            b"type" => mime_type = Some(attr.unescape_and_decode_value(reader)?),
+            b"title" => title = Some(attr.unescape_and_decode_value(reader)?),
+            b"length" => length = attr.unescape_and_decode_value(reader)?.trim().parse().ok(),
+            _ => (),
         }
     }
-    Err(GoodMorningError::Parse)
+
+    let href = href.ok_or(GoodMorningError::Parse)?;
+    let base = xml_base(event, reader, base)?;
+
+    let link = Link {
+        href: resolve_link(base.as_ref(), &href),
+        mime_type,
+        title,
+        length,
+    };
+
+    Ok((rel, link))
 }
 
 #[test]
@@ -145,18 +305,24 @@ pub fn should_parse_atom_sample_properly() {
             </entry>
         </feed>    
     "#;
-    let feed = parse_atom_feed(atom_sample).unwrap();
+    let feed = parse_atom_feed(atom_sample.as_bytes(), None).unwrap();
 
-    assert_eq!(feed.title, "Example Feed");
+    assert_eq!(feed.title.text(), "Example Feed");
     assert_eq!(feed.link, "http://example.org/");
     assert_eq!(feed.updated, "2003-12-13T18:30:02Z");
 
     let entry = feed.entries.first().unwrap();
 
-    assert_eq!(entry.title, "Atom-Powered Robots Run Amok");
+    assert_eq!(entry.title.text(), "Atom-Powered Robots Run Amok");
     assert_eq!(entry.link, "http://example.org/2003/12/13/atom03");
     assert_eq!(entry.guid, "urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a");
     assert_eq!(entry.updated, "2003-12-13T18:30:02Z");
+    assert_eq!(entry.published, None);
+    assert_eq!(
+        entry.summary.as_ref().map(|summary| summary.text()),
+        Some("Some text.".to_string())
+    );
+    assert_eq!(entry.content, None);
 }
 
 #[test]
@@ -183,14 +349,14 @@ pub fn should_fail_on_invalid_xml() {
         </feed>    
     "#;
 
-    assert!(parse_atom_feed(atom_sample).is_err());
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
 }
 
 #[test]
 pub fn should_fail_on_empty_xml() {
     let atom_sample = r#"  "#;
 
-    assert!(parse_atom_feed(atom_sample).is_err());
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
 }
 
 #[test]
@@ -210,7 +376,7 @@ pub fn should_fail_on_empty_entries() {
         </feed>    
     "#;
 
-    assert!(parse_atom_feed(atom_sample).is_err());
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
 }
 
 #[test]
@@ -231,7 +397,7 @@ pub fn should_fail_on_missing_fields() {
         </feed>    
     "#;
 
-    assert!(parse_atom_feed(atom_sample).is_err());
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
 
     let atom_sample = r#"
         <?xml version="1.0" encoding="utf-8"?>
@@ -249,5 +415,193 @@ pub fn should_fail_on_missing_fields() {
         </feed>    
     "#;
 
-    assert!(parse_atom_feed(atom_sample).is_err());
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
+}
+
+#[test]
+pub fn should_resolve_relative_links() {
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom" xml:base="/blog/">
+            <title>Example Feed</title>
+            <link href="."/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title>Atom-Powered Robots Run Amok</title>
+                <link href="/2019/post"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+            </entry>
+            <entry xml:base="http://other.example.org/archive/">
+                <title>Relative to the entry</title>
+                <link href="2003/12/13/atom03"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6b</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+            </entry>
+            <entry>
+                <title>Relative to the feed</title>
+                <link href="2003/12/13/atom04"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6c</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+            </entry>
+        </feed>
+    "#;
+
+    let base = Url::parse("http://example.org/feeds/atom.xml").unwrap();
+    let feed = parse_atom_feed(atom_sample.as_bytes(), Some(&base)).unwrap();
+
+    assert_eq!(feed.link, "http://example.org/blog/");
+    assert_eq!(feed.entries[0].link, "http://example.org/2019/post");
+    assert_eq!(
+        feed.entries[1].link,
+        "http://other.example.org/archive/2003/12/13/atom03"
+    );
+    assert_eq!(
+        feed.entries[2].link,
+        "http://example.org/blog/2003/12/13/atom04"
+    );
+}
+
+#[test]
+pub fn should_pick_alternate_link_and_keep_other_relations() {
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <title>Example Feed</title>
+            <link rel="self" type="application/atom+xml" href="http://example.org/feed.atom"/>
+            <link rel="alternate" type="text/html" href="http://example.org/"/>
+            <link rel="via" href="http://aggregator.example.com/"/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title>Atom-Powered Robots Run Amok</title>
+                <link href="http://example.org/2003/12/13/atom03"/>
+                <link rel="http://www.iana.org/assignments/relation/alternate" type="text/html"
+                    href="http://example.org/2003/12/13/atom03.html"/>
+                <link rel="enclosure" type="audio/mpeg" length="1337"
+                    href="http://example.org/audio/ph34r_my_podcast.mp3"/>
+                <link rel="related" title="Part one" href="http://example.org/2003/12/12/atom02"/>
+                <link rel="replies" type="application/atom+xml" href="http://example.org/2003/12/13/atom03/comments"/>
+                <link rel="self" href="http://example.org/2003/12/13/atom03.atom"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+                <source>
+                    <id>http://original.example.com/feed</id>
+                    <link rel="alternate" href="http://original.example.com/"/>
+                </source>
+            </entry>
+        </feed>
+    "#;
+
+    let feed = parse_atom_feed(atom_sample.as_bytes(), None).unwrap();
+
+    assert_eq!(feed.link, "http://example.org/");
+
+    let entry = feed.entries.first().unwrap();
+
+    assert_eq!(entry.link, "http://example.org/2003/12/13/atom03");
+    assert_eq!(
+        entry.enclosures,
+        vec![Link {
+            href: "http://example.org/audio/ph34r_my_podcast.mp3".to_string(),
+            mime_type: Some("audio/mpeg".to_string()),
+            title: None,
+            length: Some(1337),
+        }]
+    );
+}
+
+#[test]
+pub fn should_fall_back_on_via_and_related_links() {
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <title>Example Feed</title>
+            <link rel="self" href="http://example.org/feed.atom"/>
+            <link rel="via" href="http://aggregator.example.com/"/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title>Part two</title>
+                <link rel="self" href="http://example.org/2003/12/13/atom03.atom"/>
+                <link rel="related" href="http://example.org/2003/12/12/atom02"/>
+                <link rel="via" href="http://original.example.com/2003/12/13/robots"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+            </entry>
+            <entry>
+                <title>Part one</title>
+                <link rel="related" href="http://example.org/2003/12/12/atom02"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6b</id>
+                <updated>2003-12-12T18:30:02Z</updated>
+            </entry>
+        </feed>
+    "#;
+
+    let feed = parse_atom_feed(atom_sample.as_bytes(), None).unwrap();
+
+    assert_eq!(feed.link, "http://aggregator.example.com/");
+    assert_eq!(
+        feed.entries[0].link,
+        "http://original.example.com/2003/12/13/robots"
+    );
+    assert_eq!(feed.entries[1].link, "http://example.org/2003/12/12/atom02");
+}
+
+#[test]
+pub fn should_not_use_replies_link_as_permalink() {
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <title>Example Feed</title>
+            <link href="http://example.org/"/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title>Atom-Powered Robots Run Amok</title>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+                <link rel="replies" href="http://example.org/2003/12/13/atom03/comments"/>
+            </entry>
+        </feed>
+    "#;
+
+    assert!(parse_atom_feed(atom_sample.as_bytes(), None).is_err());
+}
+
+#[test]
+pub fn should_decode_html_and_xhtml_text_constructs() {
+    let atom_sample = r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+            <title type="html">Example &amp;amp; Feed</title>
+            <link href="http://example.org/"/>
+            <updated>2003-12-13T18:30:02Z</updated>
+            <entry>
+                <title type="html">Atom-Powered &lt;em&gt;Robots&lt;/em&gt; Run Amok</title>
+                <link href="http://example.org/2003/12/13/atom03"/>
+                <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+                <updated>2003-12-13T18:30:02Z</updated>
+                <summary type="xhtml">
+                    <div xmlns="http://www.w3.org/1999/xhtml">
+                        <p>Some <strong>bold</strong> text.</p>
+                    </div>
+                </summary>
+                <content type="html"><![CDATA[<p>The <em>whole</em> text.</p>]]></content>
+            </entry>
+        </feed>
+    "#;
+
+    let feed = parse_atom_feed(atom_sample.as_bytes(), None).unwrap();
+    assert_eq!(feed.title.text(), "Example & Feed");
+
+    let entry = feed.entries.first().unwrap();
+
+    assert_eq!(entry.title.text(), "Atom-Powered Robots Run Amok");
+    assert_eq!(entry.title.value, "Atom-Powered <em>Robots</em> Run Amok");
+
+    let summary = entry.summary.as_ref().unwrap();
+    assert_eq!(summary.value, "<p>Some <strong>bold</strong> text.</p>");
+    assert_eq!(summary.text(), "Some bold text.");
+
+    let content = entry.content.as_ref().unwrap();
+    assert_eq!(content.value, "<p>The <em>whole</em> text.</p>");
+    assert_eq!(content.text(), "The whole text.");
 }
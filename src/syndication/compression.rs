@@ -0,0 +1,98 @@
+use brotli::Decompressor;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+use crate::common::GoodMorningError;
+use crate::http::read_limited;
+
+/// `Accept-Encoding` of feed requests
+pub const ACCEPTED_ENCODINGS: &str = "gzip, deflate, br";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Undo the `Content-Encoding` of a body, then the gzip of documents like `.xml.gz` files.
+/// Decompressed bodies are held to `max` bytes as well.
+pub fn decompress(
+    bytes: Vec<u8>,
+    content_encoding: Option<&str>,
+    max: u64,
+) -> Result<Vec<u8>, GoodMorningError> {
+    let encodings: Vec<String> = content_encoding
+        .unwrap_or("")
+        .split(',')
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+        .collect();
+
+    let mut bytes = bytes;
+
+    // encodings are listed in the order they were applied
+    for encoding in encodings.iter().rev() {
+        bytes = match encoding.as_str() {
+            "gzip" | "x-gzip" => read_limited(GzDecoder::new(&bytes[..]), max)?,
+            "deflate" => inflate(&bytes, max)?,
+            "br" => read_limited(Decompressor::new(&bytes[..], 4096), max)?,
+            _ => return Err(GoodMorningError::UnsupportedEncoding(encoding.clone())),
+        };
+    }
+
+    if bytes.starts_with(&GZIP_MAGIC) {
+        bytes = read_limited(GzDecoder::new(&bytes[..]), max)?;
+    }
+
+    Ok(bytes)
+}
+
+// deflate is meant to be wrapped in zlib, some servers send it raw
+fn inflate(bytes: &[u8], max: u64) -> Result<Vec<u8>, GoodMorningError> {
+    match read_limited(ZlibDecoder::new(bytes), max) {
+        Err(GoodMorningError::IoError(_)) => read_limited(DeflateDecoder::new(bytes), max),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+fn compress<W: std::io::Write>(mut encoder: W, bytes: &[u8]) -> W {
+    encoder.write_all(bytes).unwrap();
+    encoder
+}
+
+#[test]
+pub fn should_decompress_content_encodings() {
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    let xml = b"<rss version=\"2.0\"><channel><title>Liftoff News</title></channel></rss>";
+
+    let gzip = compress(GzEncoder::new(Vec::new(), Compression::default()), xml)
+        .finish()
+        .unwrap();
+    let zlib = compress(ZlibEncoder::new(Vec::new(), Compression::default()), xml)
+        .finish()
+        .unwrap();
+    let deflate = compress(DeflateEncoder::new(Vec::new(), Compression::default()), xml)
+        .finish()
+        .unwrap();
+    let brotli =
+        compress(brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22), xml).into_inner();
+
+    assert_eq!(
+        decompress(gzip.clone(), Some("gzip"), 1024).unwrap(),
+        xml.to_vec()
+    );
+    assert_eq!(
+        decompress(zlib, Some("deflate"), 1024).unwrap(),
+        xml.to_vec()
+    );
+    assert_eq!(
+        decompress(deflate, Some("deflate"), 1024).unwrap(),
+        xml.to_vec()
+    );
+    assert_eq!(decompress(brotli, Some("br"), 1024).unwrap(), xml.to_vec());
+    assert_eq!(decompress(xml.to_vec(), None, 1024).unwrap(), xml.to_vec());
+
+    // served as a .xml.gz file
+    assert_eq!(decompress(gzip.clone(), None, 1024).unwrap(), xml.to_vec());
+
+    assert!(decompress(gzip.clone(), Some("compress"), 1024).is_err());
+    assert!(decompress(gzip, Some("gzip"), 16).is_err());
+}
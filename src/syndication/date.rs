@@ -0,0 +1,91 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+// Formats seen in the wild that are neither rfc 2822 nor rfc 3339
+const NAIVE_DATE_TIME_FORMATS: [&str; 3] =
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// Parse a feed date (rss uses rfc 2822, atom uses rfc 3339).
+/// Dates without timezone are considered UTC.
+pub fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date);
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date);
+    }
+
+    let utc = FixedOffset::east_opt(0).unwrap();
+
+    for format in NAIVE_DATE_TIME_FORMATS.iter() {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(utc.from_utc_datetime(&date));
+        }
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|date| utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+/// Sort articles newest first.
+/// Articles without a (parsable) date are kept in document order after the dated ones.
+pub fn sort_by_date<T, F>(articles: &mut [T], date_of: F)
+where
+    F: Fn(&T) -> Option<&str>,
+{
+    articles.sort_by_key(|article| {
+        std::cmp::Reverse(
+            date_of(article)
+                .and_then(parse_date)
+                .map(|d| d.with_timezone(&Utc)),
+        )
+    });
+}
+
+#[test]
+pub fn should_parse_rfc2822_dates() {
+    let date = parse_date("Tue, 10 Jun 2003 04:00:00 GMT").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-06-10T04:00:00+00:00");
+
+    let date = parse_date("Tue, 10 Jun 2003 04:00:00 +0200").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-06-10T04:00:00+02:00");
+}
+
+#[test]
+pub fn should_parse_rfc3339_dates() {
+    let date = parse_date("2003-12-13T18:30:02Z").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-12-13T18:30:02+00:00");
+
+    let date = parse_date(" 2003-12-13T18:30:02.25+01:00 ").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-12-13T18:30:02.250+01:00");
+}
+
+#[test]
+pub fn should_parse_naive_dates_as_utc() {
+    let date = parse_date("2003-12-13 18:30:02").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-12-13T18:30:02+00:00");
+
+    let date = parse_date("2003-12-13").unwrap();
+    assert_eq!(date.to_rfc3339(), "2003-12-13T00:00:00+00:00");
+
+    assert!(parse_date("yesterday").is_none());
+}
+
+#[test]
+pub fn should_sort_newest_first_and_keep_undated_in_document_order() {
+    let mut articles = vec![
+        ("a", None),
+        ("b", Some("Tue, 20 May 2003 08:56:02 GMT")),
+        ("c", Some("not a date")),
+        ("d", Some("Tue, 27 May 2003 08:37:32 GMT")),
+        ("e", Some("2003-05-21T00:00:00+02:00")),
+    ];
+
+    sort_by_date(&mut articles, |article| article.1);
+
+    let order: Vec<&str> = articles.iter().map(|article| article.0).collect();
+    assert_eq!(order, vec!["d", "e", "b", "a", "c"]);
+}
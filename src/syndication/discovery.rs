@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+
+use url::Url;
+
+use super::html::find_tags;
+use super::{fetch, fetch_http, web_url, CheckContext};
+use crate::common::*;
+
+// Tried on every site, whatever its pages link to
+const COMMON_FEED_PATHS: [&str; 4] = ["/feed", "/rss.xml", "/atom.xml", "/index.xml"];
+
+const FEED_MIME_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedCandidate {
+    pub url: String,
+    pub title: Option<String>,
+    pub format: FeedFormat,
+}
+
+impl FeedCandidate {
+    /// None when the format isn't supported
+    pub fn kind(&self) -> Option<FeedType> {
+        match self.format {
+            FeedFormat::Rss => Some(FeedType::Rss),
+            FeedFormat::Atom => Some(FeedType::Atom),
+            FeedFormat::Json => None,
+        }
+    }
+}
+
+/// Find the feeds of a web page: the page itself if it's a feed, the feeds it links to
+/// (`<link rel="alternate">`) and the feeds found at common paths of the site.
+/// `access` is only used on the host of the page. Only `url` may be a local file, the feeds found
+/// are fetched over http(s).
+pub fn discover_feeds(
+    context: &CheckContext,
+    url: &str,
+    access: &FeedAccess,
+) -> Result<Vec<FeedCandidate>, GoodMorningError> {
+    debug!("Discovering feeds of {:?}", url);
+
+    let page = fetch(context, url, access)?;
+    let public = FeedAccess::default();
+
+    if let Some(format) = detect_format(&page.body) {
+        return Ok(vec![FeedCandidate {
+            url: page.url.to_string(),
+            title: None,
+            format,
+        }]);
+    }
+
+    let mut urls = linked_feeds(&page.body, &page.url);
+    for path in COMMON_FEED_PATHS.iter() {
+        if let Ok(url) = page.url.join(path) {
+            let url = url.to_string();
+            if !urls.iter().any(|(linked, _)| *linked == url) {
+                urls.push((url, None));
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (url, title) in urls {
+        let same_host = Url::parse(&url).ok().is_some_and(|url| {
+            url.host_str() == page.url.host_str() && url.port() == page.url.port()
+        });
+        let access = if same_host { access } else { &public };
+        let document = match web_url(&url).and_then(|url| fetch_http(context, url.as_str(), access))
+        {
+            Ok(document) => document,
+            Err(err) => {
+                debug!("No feed at {:?} ({:?})", url, err);
+                continue;
+            }
+        };
+
+        // probed paths often redirect to a linked feed
+        if !seen.insert(document.url.to_string()) {
+            continue;
+        }
+
+        if let Some(format) = detect_format(&document.body) {
+            candidates.push(FeedCandidate { url, title, format });
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Feeds advertised by the page, with their title
+fn linked_feeds(html: &str, page_url: &Url) -> Vec<(String, Option<String>)> {
+    let base = find_tags(html, "base")
+        .into_iter()
+        .filter_map(|tag| tag.attr("href").and_then(|href| page_url.join(href).ok()))
+        .next()
+        .unwrap_or_else(|| page_url.clone());
+
+    find_tags(html, "link")
+        .into_iter()
+        .filter(|tag| {
+            let is_alternate = tag.attr("rel").is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("alternate"))
+            });
+            let is_feed = tag.attr("type").is_some_and(|mime_type| {
+                let mime_type = mime_type.split(';').next().unwrap_or("").trim();
+                FEED_MIME_TYPES
+                    .iter()
+                    .any(|feed_type| feed_type.eq_ignore_ascii_case(mime_type))
+            });
+            is_alternate && is_feed
+        })
+        .filter_map(|tag| {
+            let url = base.join(tag.attr("href")?.trim()).ok()?;
+            let title = tag.attr("title").map(|title| title.to_string());
+            Some((url.to_string(), title))
+        })
+        .collect()
+}
+
+/// Sniff the format of a document from its root element
+pub fn detect_format(body: &str) -> Option<FeedFormat> {
+    let body = body.trim_start_matches('\u{feff}').trim_start();
+
+    if body.starts_with('{') {
+        return if body.contains("jsonfeed.org/version") {
+            Some(FeedFormat::Json)
+        } else {
+            None
+        };
+    }
+
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        // xml declaration, processing instructions, doctype and comments
+        if rest.starts_with('?') || rest.starts_with('!') {
+            continue;
+        }
+
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+
+        return match &rest[..name_len] {
+            "rss" => Some(FeedFormat::Rss),
+            "feed" => Some(FeedFormat::Atom),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+#[test]
+pub fn should_find_linked_feeds() {
+    let html = r#"<!DOCTYPE html>
+        <html>
+        <head>
+            <title>My blog</title>
+            <link rel="stylesheet" href="/style.css">
+            <link rel="alternate" type="application/rss+xml" title="Posts" href="/feed.xml">
+            <link rel="alternate" type="application/atom+xml; charset=utf-8" href="comments.atom">
+            <link rel="alternate" type="application/feed+json" href="https://cdn.example.com/feed.json">
+            <link rel="alternate" hreflang="fr" href="/fr/">
+        </head>
+        <body></body>
+        </html>"#;
+
+    let page_url = Url::parse("https://example.com/blog/").unwrap();
+
+    assert_eq!(
+        linked_feeds(html, &page_url),
+        vec![
+            (
+                "https://example.com/feed.xml".to_string(),
+                Some("Posts".to_string())
+            ),
+            ("https://example.com/blog/comments.atom".to_string(), None),
+            ("https://cdn.example.com/feed.json".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+pub fn should_not_follow_local_feed_links() {
+    use crate::http::{HostThrottle, RetryPolicy};
+    use reqwest::Client;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let context = CheckContext {
+        client: Client::new(),
+        parse_mode: ParseMode::Strict,
+        hosts: Arc::new(HostThrottle::new(Duration::from_secs(0))),
+        retry: RetryPolicy {
+            retries: 0,
+            base_delay: Duration::from_secs(0),
+        },
+        credentials: Arc::new(HashMap::new()),
+        max_body_size: 1024 * 1024,
+    };
+
+    // the page may be local, the feeds it links to may not
+    let candidates = discover_feeds(
+        &context,
+        "fixtures/pages/local-feeds.html",
+        &FeedAccess::default(),
+    )
+    .unwrap();
+    assert_eq!(candidates, vec![]);
+
+    // unlike the url given
+    let candidates = discover_feeds(
+        &context,
+        "fixtures/feeds/liftoff.rss",
+        &FeedAccess::default(),
+    )
+    .unwrap();
+    assert_eq!(candidates[0].format, FeedFormat::Rss);
+}
+
+#[test]
+pub fn should_detect_feed_format() {
+    assert_eq!(
+        detect_format(
+            "<?xml version=\"1.0\"?>\n<!-- hello --><rss version=\"2.0\"><channel/></rss>"
+        ),
+        Some(FeedFormat::Rss)
+    );
+    assert_eq!(
+        detect_format("\u{feff}<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>"),
+        Some(FeedFormat::Atom)
+    );
+    assert_eq!(
+        detect_format("{\"version\": \"https://jsonfeed.org/version/1\", \"items\": []}"),
+        Some(FeedFormat::Json)
+    );
+    assert_eq!(
+        detect_format("<!DOCTYPE html><html><head></head></html>"),
+        None
+    );
+    assert_eq!(detect_format(""), None);
+}
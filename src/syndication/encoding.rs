@@ -0,0 +1,136 @@
+use encoding_rs::{Encoding, UTF_8};
+
+// The xml declaration must be at the very beginning of the document
+const XML_DECLARATION_MAX_LEN: usize = 1024;
+
+/// Decode a feed body to utf-8.
+/// The encoding is detected (in order) from the BOM, the http charset and the xml declaration,
+/// and defaults to utf-8.
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = detect_encoding(bytes, content_type);
+    debug!("Decoding body as {}", encoding.name());
+
+    let (text, _encoding, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        warn!("Body contains invalid {} sequences", encoding.name());
+    }
+
+    text.into_owned()
+}
+
+pub fn detect_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _bom_len)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    content_type
+        .and_then(charset_from_content_type)
+        .or_else(|| encoding_from_xml_declaration(bytes))
+        .unwrap_or(UTF_8)
+}
+
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| {
+            let mut tokens = param.splitn(2, '=');
+            match (tokens.next(), tokens.next()) {
+                (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("charset") => {
+                    Some(value.trim().trim_matches(|c| c == '"' || c == '\''))
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+}
+
+fn encoding_from_xml_declaration(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(XML_DECLARATION_MAX_LEN)];
+    let head = String::from_utf8_lossy(head);
+    let head = head.trim_start();
+
+    if !head.starts_with("<?xml") {
+        return None;
+    }
+
+    let declaration = &head[..head.find("?>")?];
+    let value = &declaration[declaration.find("encoding")? + "encoding".len()..];
+    let value = value.trim_start().strip_prefix('=')?.trim_start();
+
+    let quote = value.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+
+    let label = &value[1..];
+    let label = &label[..label.find(quote)?];
+
+    let encoding = Encoding::for_label(label.as_bytes())?;
+
+    // A declaration read as ascii can't be telling the truth about utf-16
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        return None;
+    }
+
+    Some(encoding)
+}
+
+#[test]
+pub fn should_detect_encoding_from_bom() {
+    let body = include_bytes!("../../fixtures/encoding/utf16le-bom.xml");
+    assert_eq!(detect_encoding(body, None), encoding_rs::UTF_16LE);
+
+    // BOM wins over the http charset
+    let body = include_bytes!("../../fixtures/encoding/utf8-bom.xml");
+    assert_eq!(
+        detect_encoding(body, Some("application/rss+xml; charset=iso-8859-1")),
+        UTF_8
+    );
+
+    let text = decode_body(body, None);
+    assert!(text.starts_with("<?xml"));
+    assert!(text.contains("<title>Café crème</title>"));
+}
+
+#[test]
+pub fn should_detect_encoding_from_http_charset() {
+    let body = include_bytes!("../../fixtures/encoding/windows-1252-http.xml");
+    assert_eq!(
+        detect_encoding(body, Some("text/xml; Charset=\"windows-1252\"")),
+        encoding_rs::WINDOWS_1252
+    );
+
+    let text = decode_body(body, Some("text/xml; charset=windows-1252"));
+    assert!(text.contains("<title>L’été à Paris – 5 €</title>"));
+}
+
+#[test]
+pub fn should_detect_encoding_from_xml_declaration() {
+    let body = include_bytes!("../../fixtures/encoding/iso-8859-1-declaration.xml");
+    assert_eq!(
+        detect_encoding(body, Some("application/xml")),
+        encoding_rs::WINDOWS_1252
+    );
+
+    let text = decode_body(body, None);
+    assert!(text.contains("<title>Éditions françaises</title>"));
+
+    let body = include_bytes!("../../fixtures/encoding/utf16le-bom.xml");
+    let text = decode_body(body, None);
+    assert!(text.contains("<title>Ünïcödé</title>"));
+}
+
+#[test]
+pub fn should_default_to_utf8() {
+    assert_eq!(detect_encoding(b"<rss></rss>", None), UTF_8);
+    assert_eq!(
+        detect_encoding(b"<?xml version=\"1.0\"?><rss/>", None),
+        UTF_8
+    );
+    assert_eq!(
+        detect_encoding(b"<?xml version=\"1.0\" encoding=\"bogus\"?>", None),
+        UTF_8
+    );
+}
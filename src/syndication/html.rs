@@ -0,0 +1,131 @@
+use super::text::decode_entities;
+
+/// Html start tag, as found by `find_tags`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Find the start tags named `name` in an html document.
+/// This is a minimal scanner (html is rarely valid xml), comments are skipped.
+pub fn find_tags(html: &str, name: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        if rest.starts_with("!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + 3..],
+                None => break,
+            };
+            continue;
+        }
+
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        if name_len == 0 || !rest[..name_len].eq_ignore_ascii_case(name) {
+            continue;
+        }
+
+        let (attributes, len) = parse_attributes(&rest[name_len..]);
+        tags.push(Tag {
+            name: rest[..name_len].to_ascii_lowercase(),
+            attributes,
+        });
+        rest = &rest[name_len + len..];
+    }
+
+    tags
+}
+
+/// Returns the attributes and the length of the tag up to its closing `>`
+pub fn parse_attributes(tag: &str) -> (Vec<(String, String)>, usize) {
+    let mut attributes = Vec::new();
+    let bytes = tag.as_bytes();
+    let mut i = 0;
+
+    let skip_whitespaces = |mut i: usize| {
+        while i < bytes.len() && (bytes[i] as char).is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+
+    loop {
+        i = skip_whitespaces(i);
+        while i < bytes.len() && bytes[i] == b'/' {
+            i = skip_whitespaces(i + 1);
+        }
+        if i >= bytes.len() || bytes[i] == b'>' {
+            break;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && !b" \t\r\n=>/".contains(&bytes[i]) {
+            i += 1;
+        }
+        let name = tag[name_start..i].to_ascii_lowercase();
+
+        i = skip_whitespaces(i);
+        let mut value = "";
+        if i < bytes.len() && bytes[i] == b'=' {
+            i = skip_whitespaces(i + 1);
+            if i < bytes.len() && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let quote = bytes[i];
+                let value_start = i + 1;
+                i = value_start;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+                value = &tag[value_start..i];
+                i += 1;
+            } else {
+                let value_start = i;
+                while i < bytes.len() && !b" \t\r\n>".contains(&bytes[i]) {
+                    i += 1;
+                }
+                value = &tag[value_start..i];
+            }
+        }
+
+        if !name.is_empty() {
+            attributes.push((name, decode_entities(value)));
+        }
+    }
+
+    (attributes, i.min(tag.len()))
+}
+
+#[test]
+pub fn should_find_tags_and_their_attributes() {
+    let html = r#"<!DOCTYPE html>
+        <html><head>
+        <!-- <link rel="alternate" href="/commented-out.xml"> -->
+        <LINK REL="alternate" type='application/rss+xml' title="Posts &amp; news" href=/feed.xml>
+        <link rel="stylesheet" href="/style.css"/>
+        <linked>not a link</linked>
+        </head></html>"#;
+
+    let tags = find_tags(html, "link");
+
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[0].name, "link");
+    assert_eq!(tags[0].attr("rel"), Some("alternate"));
+    assert_eq!(tags[0].attr("type"), Some("application/rss+xml"));
+    assert_eq!(tags[0].attr("title"), Some("Posts & news"));
+    assert_eq!(tags[0].attr("href"), Some("/feed.xml"));
+    assert_eq!(tags[1].attr("href"), Some("/style.css"));
+}
@@ -0,0 +1,245 @@
+use quick_xml::events::BytesStart;
+use quick_xml::Reader;
+use url::Url;
+
+use crate::common::GoodMorningError;
+
+// Query parameters added for analytics, they don't change the page
+const TRACKING_PARAMS: [&str; 12] = [
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_hsenc",
+    "_hsmi", "mkt_tok", "ref_src",
+];
+
+/// Resolve a (possibly relative) link against the base url.
+/// The link is returned untouched when there is no base or when it can't be resolved.
+pub fn resolve_link(base: Option<&Url>, href: &str) -> String {
+    let href = href.trim();
+
+    match base.map(|base| base.join(href)) {
+        Some(Ok(url)) => url.into_string(),
+        _ => href.to_string(),
+    }
+}
+
+// FeedBurner proxies the article links of the feeds it serves through these hosts
+const REDIRECT_HOSTS: [&str; 3] = [
+    "feedproxy.google.com",
+    "feeds.feedburner.com",
+    "feedburner.google.com",
+];
+
+/// The url to store for a subscription: only the scheme and the host are lower-cased and the
+/// default port dropped (done by `Url`), servers may need the rest as given.
+/// Strings that aren't urls are only trimmed.
+pub fn clean_url(url: &str) -> String {
+    match Url::parse(url.trim()) {
+        Ok(parsed) => parsed.into_string(),
+        Err(_) => url.trim().to_string(),
+    }
+}
+
+// canonical form of a url, for comparisons only: tracking parameters and the trailing slash of
+// the path are removed too
+fn normalize_url(url: &str) -> String {
+    let mut parsed = match Url::parse(url.trim()) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.trim().to_string(),
+    };
+
+    remove_tracking_params(&mut parsed);
+
+    if parsed.path().len() > 1 && parsed.path().ends_with('/') {
+        let path = parsed.path().trim_end_matches('/').to_string();
+        parsed.set_path(&path);
+    }
+
+    parsed.into_string()
+}
+
+/// The url without its tracking parameters, strings that aren't urls are left untouched.
+pub fn strip_tracking_params(url: &str) -> String {
+    match Url::parse(url.trim()) {
+        Ok(mut parsed) => {
+            remove_tracking_params(&mut parsed);
+            parsed.into_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Whether the link goes through FeedBurner before reaching the article
+pub fn is_feedburner_redirect(url: &str) -> bool {
+    Url::parse(url).ok().is_some_and(|parsed| {
+        parsed
+            .host_str()
+            .is_some_and(|host| REDIRECT_HOSTS.contains(&host))
+            && parsed.path().starts_with("/~r/")
+    })
+}
+
+fn remove_tracking_params(parsed: &mut Url) {
+    let params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    // the query is only rewritten when needed, to keep its encoding
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else if params.len() != parsed.query_pairs().count() {
+        parsed.query_pairs_mut().clear().extend_pairs(params);
+    }
+}
+
+/// Compare urls with this key to tell whether they're the same page: they're normalized
+/// and http is considered the same as https.
+pub fn url_key(url: &str) -> String {
+    let url = normalize_url(url);
+    match url.strip_prefix("http://") {
+        Some(rest) => format!("https://{}", rest),
+        None => url,
+    }
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+/// Compute the base url in scope for an element, honoring its `xml:base` attribute.
+pub fn xml_base<B: std::io::BufRead>(
+    event: &BytesStart,
+    reader: &Reader<B>,
+    parent: Option<&Url>,
+) -> Result<Option<Url>, GoodMorningError> {
+    for attr in event.attributes() {
+        let attr = attr?;
+        if attr.key == b"xml:base" {
+            let value = attr.unescape_and_decode_value(reader)?;
+            let base = match parent {
+                Some(parent) => parent.join(value.trim()),
+                None => Url::parse(value.trim()),
+            };
+
+            return match base {
+                Ok(base) => Ok(Some(base)),
+                Err(err) => {
+                    warn!("Ignoring invalid xml:base {:?} ({:?})", value, err);
+                    Ok(parent.cloned())
+                }
+            };
+        }
+    }
+
+    Ok(parent.cloned())
+}
+
+#[test]
+pub fn should_resolve_relative_links() {
+    let base = Url::parse("http://example.org/blog/feed.xml").unwrap();
+
+    assert_eq!(
+        resolve_link(Some(&base), "/2019/post"),
+        "http://example.org/2019/post"
+    );
+    assert_eq!(
+        resolve_link(Some(&base), "post.html"),
+        "http://example.org/blog/post.html"
+    );
+    assert_eq!(
+        resolve_link(Some(&base), " https://other.org/a "),
+        "https://other.org/a"
+    );
+    assert_eq!(resolve_link(None, "/2019/post"), "/2019/post");
+}
+
+#[test]
+pub fn should_normalize_urls() {
+    assert_eq!(
+        normalize_url("HTTPS://Example.org:443/blog/post/?utm_source=rss&utm_medium=feed"),
+        "https://example.org/blog/post"
+    );
+    assert_eq!(
+        normalize_url("https://example.org/search?q=rust+lang&fbclid=abc"),
+        "https://example.org/search?q=rust+lang"
+    );
+    assert_eq!(
+        normalize_url("https://example.org/search?q=rust%20lang"),
+        "https://example.org/search?q=rust%20lang"
+    );
+    assert_eq!(
+        normalize_url("https://example.org/"),
+        "https://example.org/"
+    );
+    assert_eq!(normalize_url(" feeds/build.xml "), "feeds/build.xml");
+
+    assert_eq!(
+        clean_url(" HTTPS://Example.org:443/Blog/post/?utm_source=rss&Q=A "),
+        "https://example.org/Blog/post/?utm_source=rss&Q=A"
+    );
+    assert_eq!(clean_url(" feeds/build.xml "), "feeds/build.xml");
+
+    assert_eq!(url_key("http://x.com/feed"), url_key("https://x.com/feed/"));
+    assert_eq!(
+        url_key("HTTPS://X.com/feed"),
+        url_key("https://x.com/feed/")
+    );
+    assert_ne!(
+        url_key("https://x.com/feed"),
+        url_key("https://x.com/feed.xml")
+    );
+}
+
+#[test]
+pub fn should_strip_tracking_params() {
+    assert_eq!(
+        strip_tracking_params("https://example.org/post/?utm_source=rss&id=3&mc_eid=x"),
+        "https://example.org/post/?id=3"
+    );
+    assert_eq!(
+        strip_tracking_params("https://example.org/post?fbclid=abc#comments"),
+        "https://example.org/post#comments"
+    );
+    assert_eq!(
+        strip_tracking_params("/relative?utm_source=rss"),
+        "/relative?utm_source=rss"
+    );
+
+    assert!(is_feedburner_redirect(
+        "http://feedproxy.google.com/~r/Blog/~3/AbCd/post.html"
+    ));
+    assert!(!is_feedburner_redirect("http://feeds.feedburner.com/Blog"));
+    assert!(!is_feedburner_redirect("https://example.org/~r/post"));
+}
+
+#[test]
+pub fn should_scope_xml_base() {
+    let xml = r#"<feed xml:base="/blog/"><entry xml:base="2019/"><link/></entry></feed>"#;
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+
+    let document = Url::parse("http://example.org/feeds/atom.xml").unwrap();
+    let mut bases = Vec::new();
+    let mut base = Some(document);
+
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            quick_xml::events::Event::Start(ref e) | quick_xml::events::Event::Empty(ref e) => {
+                base = xml_base(e, &reader, base.as_ref()).unwrap();
+                bases.push(base.as_ref().unwrap().to_string());
+            }
+            quick_xml::events::Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    assert_eq!(
+        bases,
+        vec![
+            "http://example.org/blog/",
+            "http://example.org/blog/2019/",
+            "http://example.org/blog/2019/"
+        ]
+    );
+}
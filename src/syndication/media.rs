@@ -0,0 +1,114 @@
+use quick_xml::events::BytesStart;
+use quick_xml::Reader;
+use url::Url;
+
+use super::link::resolve_link;
+use crate::common::{Enclosure, GoodMorningError};
+
+/// Podcast and media rss extensions of an item or an entry.
+/// The usual `itunes:` and `media:` prefixes are expected.
+#[derive(Debug, Default)]
+pub struct Media {
+    pub enclosures: Vec<Enclosure>,
+    pub thumbnail: Option<String>,
+    pub duration: Option<u64>,
+}
+
+impl Media {
+    /// Read the media element whose only information is in its attributes.
+    /// Returns false when the element isn't one of them.
+    pub fn read_element<B: std::io::BufRead>(
+        &mut self,
+        event: &BytesStart,
+        reader: &Reader<B>,
+        base: Option<&Url>,
+    ) -> Result<bool, GoodMorningError> {
+        match event.name() {
+            b"enclosure" => {
+                let url = attr(event, reader, b"url")?;
+                if let Some(url) = url {
+                    self.enclosures.push(Enclosure {
+                        url: resolve_link(base, &url),
+                        mime_type: attr(event, reader, b"type")?,
+                        length: attr(event, reader, b"length")?.and_then(|l| l.trim().parse().ok()),
+                        duration: None,
+                    })
+                }
+            }
+            b"media:content" => {
+                let url = attr(event, reader, b"url")?;
+                if let Some(url) = url {
+                    let mime_type = match attr(event, reader, b"type")? {
+                        Some(mime_type) => Some(mime_type),
+                        None => attr(event, reader, b"medium")?,
+                    };
+                    self.enclosures.push(Enclosure {
+                        url: resolve_link(base, &url),
+                        mime_type,
+                        length: attr(event, reader, b"fileSize")?
+                            .and_then(|l| l.trim().parse().ok()),
+                        duration: attr(event, reader, b"duration")?
+                            .and_then(|d| parse_duration(&d)),
+                    })
+                }
+            }
+            b"media:thumbnail" => {
+                if self.thumbnail.is_none() {
+                    let url = attr(event, reader, b"url")?;
+                    self.thumbnail = url.map(|url| resolve_link(base, &url))
+                }
+            }
+            b"itunes:image" => {
+                let href = attr(event, reader, b"href")?;
+                if let Some(href) = href {
+                    self.thumbnail = Some(resolve_link(base, &href))
+                }
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+pub fn attr<B: std::io::BufRead>(
+    event: &BytesStart,
+    reader: &Reader<B>,
+    name: &[u8],
+) -> Result<Option<String>, GoodMorningError> {
+    for attr in event.attributes() {
+        let attr = attr?;
+        if attr.key == name {
+            return Ok(Some(attr.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse an `itunes:duration` (`[[hh:]mm:]ss`) to seconds.
+pub fn parse_duration(value: &str) -> Option<u64> {
+    let mut seconds = 0;
+
+    for (i, part) in value.trim().split(':').enumerate() {
+        if i > 2 {
+            return None;
+        }
+        let part: f64 = part.trim().parse().ok()?;
+        if part < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60 + part as u64;
+    }
+
+    Some(seconds)
+}
+
+#[test]
+pub fn should_parse_durations() {
+    assert_eq!(parse_duration("42"), Some(42));
+    assert_eq!(parse_duration("3600.5"), Some(3600));
+    assert_eq!(parse_duration("07:04"), Some(424));
+    assert_eq!(parse_duration("1:02:03"), Some(3723));
+    assert_eq!(parse_duration("1:02:03:04"), None);
+    assert_eq!(parse_duration("an hour"), None);
+}
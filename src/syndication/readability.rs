@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use super::html::parse_attributes;
+use super::text::decode_entities;
+
+// Never part of the article
+const REMOVED_TAGS: [&str; 12] = [
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form",
+    "iframe", "svg", "button",
+];
+
+// Their content isn't markup, it's skipped up to their end tag
+const RAW_TEXT_TAGS: [&str; 4] = ["script", "style", "textarea", "template"];
+
+const VOID_TAGS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// Closed by the opening of a sibling, their end tag being optional
+const SELF_CLOSING_TAGS: [&str; 8] = ["p", "li", "dt", "dd", "tr", "td", "th", "option"];
+
+const BLOCK_TAGS: [&str; 24] = [
+    "address",
+    "article",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "main",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+// Matched against the class and the id of the elements, like readability does
+const UNLIKELY: &str = "(?i)ad-break|\\bads?\\b|advert|agegate|banner|breadcrumb|combx|comment|community|cookie|disqus|extra|footer|gdpr|header|legends|menu|newsletter|pager|pagination|popup|related|remark|replies|rss|share|shoutbox|sidebar|skyscraper|social|sponsor|subscribe|supplemental|tools";
+const LIKELY: &str = "(?i)article|body|column|content|entry|main|post|story|text";
+
+// Texts shorter than that are not paragraphs
+const MIN_PARAGRAPH_LEN: usize = 25;
+
+struct Node {
+    /// Empty for text nodes
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn attr(&self, name: &str) -> &str {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or("", |(_, value)| value.as_str())
+    }
+
+    fn is_block(&self) -> bool {
+        BLOCK_TAGS.contains(&self.name.as_str())
+    }
+}
+
+/// Readability-like extraction of the main text of an html page, the page of an article.
+/// Paragraphs are separated by blank lines, `None` when no part of the page looks like text.
+pub fn extract_article_text(html: &str) -> Option<String> {
+    Page::parse(html).article_text()
+}
+
+struct Page {
+    nodes: Vec<Node>,
+    unlikely: Regex,
+    likely: Regex,
+}
+
+impl Page {
+    /// Lenient html parsing: unknown end tags are ignored and the unclosed elements are closed
+    /// with their parent.
+    fn parse(html: &str) -> Page {
+        let mut page = Page {
+            nodes: Vec::new(),
+            unlikely: Regex::new(UNLIKELY).unwrap(),
+            likely: Regex::new(LIKELY).unwrap(),
+        };
+        page.add_node("#document", Vec::new(), "", None);
+
+        let mut open = vec![0];
+        let mut rest = html;
+
+        while let Some(start) = rest.find('<') {
+            page.add_text(*open.last().unwrap(), &rest[..start]);
+            rest = &rest[start + 1..];
+
+            if rest.starts_with("!--") {
+                rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+                continue;
+            }
+            if rest.starts_with('!') || rest.starts_with('?') {
+                rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+                continue;
+            }
+
+            let closing = rest.starts_with('/');
+            let tag = rest.trim_start_matches('/');
+            let name_len = tag
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(tag.len());
+            if name_len == 0 {
+                page.add_text(*open.last().unwrap(), "<");
+                continue;
+            }
+            let name = tag[..name_len].to_ascii_lowercase();
+
+            if closing {
+                if let Some(position) = open.iter().rposition(|&i| page.nodes[i].name == name) {
+                    open.truncate(position.max(1));
+                }
+                rest = tag.find('>').map_or("", |end| &tag[end + 1..]);
+                continue;
+            }
+
+            let (attributes, len) = parse_attributes(&tag[name_len..]);
+            rest = &tag[name_len + len..];
+            rest = rest.strip_prefix('>').unwrap_or(rest);
+
+            if RAW_TEXT_TAGS.contains(&name.as_str()) {
+                let end_tag = format!("</{}", name);
+                rest = rest
+                    .to_ascii_lowercase()
+                    .find(&end_tag)
+                    .map_or("", |end| &rest[end..]);
+                continue;
+            }
+
+            let parent = *open.last().unwrap();
+            let closes_parent = SELF_CLOSING_TAGS.contains(&page.nodes[parent].name.as_str())
+                && (page.nodes[parent].name == name
+                    || (page.nodes[parent].name == "p" && BLOCK_TAGS.contains(&name.as_str())));
+            if closes_parent && open.len() > 1 {
+                open.pop();
+            }
+
+            let parent = *open.last().unwrap();
+            let index = page.add_node(&name, attributes, "", Some(parent));
+            if !VOID_TAGS.contains(&name.as_str()) {
+                open.push(index);
+            }
+        }
+
+        page.add_text(*open.last().unwrap(), rest);
+        page
+    }
+
+    fn add_node(
+        &mut self,
+        name: &str,
+        attributes: Vec<(String, String)>,
+        text: &str,
+        parent: Option<usize>,
+    ) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            attributes,
+            text: text.to_string(),
+            parent,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(index);
+        }
+        index
+    }
+
+    fn add_text(&mut self, parent: usize, text: &str) {
+        if !text.is_empty() {
+            self.add_node("", Vec::new(), &decode_entities(text), Some(parent));
+        }
+    }
+
+    /// Score the elements holding paragraphs and keep the best one, with its siblings looking
+    /// like part of the article.
+    fn article_text(&self) -> Option<String> {
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for index in self.elements() {
+            let node = &self.nodes[index];
+            let is_paragraph = matches!(node.name.as_str(), "p" | "pre" | "td" | "blockquote")
+                || (node.name == "div"
+                    && !node
+                        .children
+                        .iter()
+                        .any(|&child| self.nodes[child].is_block()));
+            if !is_paragraph {
+                continue;
+            }
+
+            let text = self.text_len(index);
+            if text < MIN_PARAGRAPH_LEN {
+                continue;
+            }
+
+            let commas = self.inner_text(index).matches(',').count();
+            let score = 1.0 + commas as f64 + (text / 100).min(3) as f64;
+
+            // the grand parent gets half of the score
+            let grand_parent = node.parent.and_then(|parent| self.nodes[parent].parent);
+            for (ancestor, share) in [(node.parent, 1.0), (grand_parent, 0.5)].iter() {
+                if let Some(ancestor) = *ancestor {
+                    *scores
+                        .entry(ancestor)
+                        .or_insert_with(|| self.initial_score(ancestor)) += score * share;
+                }
+            }
+        }
+
+        let (top, top_score) = scores
+            .iter()
+            .map(|(&index, score)| (index, score * (1.0 - self.link_density(index))))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+
+        let threshold = (top_score * 0.2).max(10.0);
+        let siblings = match self.nodes[top].parent {
+            Some(parent) => self.nodes[parent].children.clone(),
+            None => vec![top],
+        };
+
+        let mut paragraphs = Vec::new();
+        for sibling in siblings.into_iter() {
+            let node = &self.nodes[sibling];
+            if node.name.is_empty() || self.is_removed(sibling) {
+                continue;
+            }
+
+            let score = scores
+                .get(&sibling)
+                .map_or(0.0, |score| score * (1.0 - self.link_density(sibling)));
+            let included = sibling == top
+                || score >= threshold
+                || (node.name == "p"
+                    && self.text_len(sibling) > 80
+                    && self.link_density(sibling) < 0.25);
+            if included {
+                self.write_paragraphs(sibling, &mut paragraphs, &mut String::new());
+            }
+        }
+
+        if paragraphs.is_empty() {
+            None
+        } else {
+            Some(paragraphs.join("\n\n"))
+        }
+    }
+
+    // the elements not removed, nor inside removed ones
+    fn elements(&self) -> Vec<usize> {
+        let mut elements = Vec::new();
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            if self.nodes[index].name.is_empty() || self.is_removed(index) {
+                continue;
+            }
+            elements.push(index);
+            stack.extend(self.nodes[index].children.iter().rev());
+        }
+
+        elements
+    }
+
+    fn is_removed(&self, index: usize) -> bool {
+        let node = &self.nodes[index];
+        if REMOVED_TAGS.contains(&node.name.as_str()) {
+            return true;
+        }
+        if matches!(
+            node.name.as_str(),
+            "#document" | "html" | "body" | "article" | "main"
+        ) {
+            return false;
+        }
+
+        let names = format!("{} {}", node.attr("class"), node.attr("id"));
+        self.unlikely.is_match(&names) && !self.likely.is_match(&names)
+    }
+
+    fn initial_score(&self, index: usize) -> f64 {
+        let node = &self.nodes[index];
+        let tag = match node.name.as_str() {
+            "div" | "article" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+
+        let names = format!("{} {}", node.attr("class"), node.attr("id"));
+        let class = if self.likely.is_match(&names) {
+            25.0
+        } else if self.unlikely.is_match(&names) {
+            -25.0
+        } else {
+            0.0
+        };
+
+        tag + class
+    }
+
+    fn inner_text(&self, index: usize) -> String {
+        let node = &self.nodes[index];
+        if node.name.is_empty() {
+            return node.text.clone();
+        }
+        if self.is_removed(index) {
+            return String::new();
+        }
+
+        node.children
+            .iter()
+            .map(|&child| self.inner_text(child))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn text_len(&self, index: usize) -> usize {
+        self.inner_text(index)
+            .split_whitespace()
+            .map(str::len)
+            .sum()
+    }
+
+    // share of the text of the element that is in links
+    fn link_density(&self, index: usize) -> f64 {
+        let text = self.text_len(index);
+        if text == 0 {
+            return 0.0;
+        }
+
+        let mut links = 0;
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            if self.nodes[current].name == "a" {
+                links += self.text_len(current);
+            } else {
+                stack.extend(self.nodes[current].children.iter());
+            }
+        }
+
+        links as f64 / text as f64
+    }
+
+    // block elements start new paragraphs, lists of links in the article are left out
+    fn write_paragraphs(&self, index: usize, paragraphs: &mut Vec<String>, current: &mut String) {
+        let node = &self.nodes[index];
+        if node.name.is_empty() {
+            current.push_str(&node.text);
+            return;
+        }
+        if self.is_removed(index) {
+            return;
+        }
+
+        let is_block = node.is_block() || node.name == "td";
+        if is_block
+            && matches!(node.name.as_str(), "div" | "ul" | "ol" | "table")
+            && self.link_density(index) > 0.5
+        {
+            return;
+        }
+
+        if is_block || node.name == "br" {
+            flush_paragraph(paragraphs, current);
+        }
+        for &child in node.children.iter() {
+            self.write_paragraphs(child, paragraphs, current);
+        }
+        if is_block {
+            flush_paragraph(paragraphs, current);
+        }
+    }
+}
+
+fn flush_paragraph(paragraphs: &mut Vec<String>, current: &mut String) {
+    let paragraph = current.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+    current.clear();
+}
+
+#[test]
+pub fn should_extract_the_text_of_a_blog_post() {
+    let html = include_str!("../../fixtures/pages/blog-post.html");
+    let text = extract_article_text(html).unwrap();
+    let paragraphs: Vec<&str> = text.split("\n\n").collect();
+
+    assert_eq!(paragraphs[0], "Writing a feed reader in Rust");
+    assert_eq!(paragraphs[1], "Posted by Sally Ride on 2019-03-02");
+    assert!(paragraphs[2].starts_with("Every morning, I used to open a dozen tabs"));
+    assert!(text.contains("storing their history in SQLite."));
+    assert!(text.contains("no server is hammered & nobody gets angry."));
+    assert!(text.contains("let client = Client::builder().build()?;"));
+    assert!(text.ends_with("compared with the one seen during the previous run."));
+
+    for left_out in &[
+        "Archive",
+        "Tweet",
+        "Parsing Atom",
+        "newsletter",
+        "Great post",
+        "rights",
+    ] {
+        assert!(!text.contains(left_out), "{} in {}", left_out, text);
+    }
+}
+
+#[test]
+pub fn should_extract_the_text_of_a_table_layout() {
+    let html = include_str!("../../fixtures/pages/news-article.html");
+    let text = extract_article_text(html).unwrap();
+    let paragraphs: Vec<&str> = text.split("\n\n").collect();
+
+    assert_eq!(
+        paragraphs,
+        vec![
+            "The three astronauts landed in the steppe of Kazakhstan early on Tuesday, after a journey of three and a half hours from the station, officials said.",
+            "Recovery teams reached the capsule within minutes, helping the crew out and into reclining chairs, as is customary after a long stay in weightlessness.",
+            "During their mission, the crew conducted more than 200 experiments, ranging from the growth of crystals to the behaviour of fluids, and performed two spacewalks.",
+            "«It feels good to be home», the commander told reporters, before being flown to the training centre for medical checks.",
+        ]
+    );
+}
+
+#[test]
+pub fn should_not_extract_pages_without_text() {
+    assert_eq!(extract_article_text(""), None);
+    assert_eq!(
+        extract_article_text("<html><body><a href=/>Home</a><p>Short</p></body></html>"),
+        None
+    );
+}
@@ -1,11 +1,18 @@
+use std::io::BufRead;
+
 use quick_xml::events::Event;
 use quick_xml::Reader;
+use url::Url;
 
-use crate::common::GoodMorningError;
+use super::link::{resolve_link, xml_base};
+use super::media::{parse_duration, Media};
+use super::text::read_text_content;
+use crate::common::{GoodMorningError, ParseMode, ParseWarning};
 
 #[derive(Debug)]
 pub struct Feed {
     pub channels: Vec<Channel>,
+    pub warnings: Vec<ParseWarning>,
 }
 
 #[derive(Debug)]
@@ -16,56 +23,80 @@ pub struct Channel {
     pub items: Vec<Item>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Item {
     pub title: Option<String>,
+    pub description: Option<String>,
     pub pub_date: Option<String>,
     pub guid: Option<String>,
     pub link: Option<String>,
+    pub author: Option<String>,
+    pub categories: Vec<String>,
+    pub media: Media,
 }
 
-fn parse_item<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Item, GoodMorningError> {
+// Returns the item along with the first error, if any. The fields after an invalid one are
+// still read, up to the end of the item so that they aren't taken for channel fields.
+fn parse_item<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+    base: Option<&Url>,
+) -> (Item, Option<GoodMorningError>) {
     let mut buf = Vec::new();
-
-    let mut title: Option<String> = None;
-    let mut pub_date: Option<String> = None;
-    let mut link: Option<String> = None;
-    let mut guid: Option<String> = None;
+    let mut item = Item::default();
+    let mut error = None;
 
     loop {
-        match reader.read_event(&mut buf) {
+        let field = match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"title" => title = Some(reader.read_text(b"title", &mut buf)?),
-                b"pubDate" => pub_date = Some(reader.read_text(b"pubDate", &mut buf)?),
-                b"link" => link = Some(reader.read_text(b"link", &mut buf)?),
-                b"guid" => guid = Some(reader.read_text(b"guid", &mut buf)?),
-                _ => (),
-            },
-            Ok(Event::End(ref e)) => {
-                if b"item" == e.name() {
-                    break;
+                b"title" => read_text_content(reader, b"title").map(|t| item.title = Some(t)),
+                b"description" => {
+                    read_text_content(reader, b"description").map(|d| item.description = Some(d))
                 }
-            }
+                b"pubDate" => {
+                    read_text_content(reader, b"pubDate").map(|d| item.pub_date = Some(d))
+                }
+                b"link" => read_text_content(reader, b"link")
+                    .map(|href| item.link = Some(resolve_link(base, &href))),
+                b"guid" => read_text_content(reader, b"guid").map(|g| item.guid = Some(g)),
+                b"author" => read_text_content(reader, b"author").map(|a| item.author = Some(a)),
+                b"dc:creator" => {
+                    read_text_content(reader, b"dc:creator").map(|a| item.author = Some(a))
+                }
+                b"category" => {
+                    read_text_content(reader, b"category").map(|c| item.categories.push(c))
+                }
+                b"itunes:duration" => read_text_content(reader, b"itunes:duration")
+                    .map(|d| item.media.duration = parse_duration(&d)),
+                _ => item.media.read_element(e, reader, base).map(|_| ()),
+            },
+            Ok(Event::Empty(ref e)) => item.media.read_element(e, reader, base).map(|_| ()),
+            Ok(Event::End(ref e)) if b"item" == e.name() => break,
             Ok(Event::Eof) => break,
-            Err(e) => return Err(GoodMorningError::XmlParse(e)),
-            _ => (),
+            // the document itself is broken
+            Err(e) => return (item, Some(GoodMorningError::XmlParse(e))),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = field {
+            error.get_or_insert(err);
         }
 
         // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
         buf.clear();
     }
 
-    Ok(Item {
-        title,
-        pub_date,
-        guid,
-        link,
-    })
+    (item, error)
 }
 
-fn parse_channel<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Channel, GoodMorningError> {
+fn parse_channel<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+    base: Option<&Url>,
+    mode: ParseMode,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<Channel, GoodMorningError> {
     let mut buf = Vec::new();
     let mut items = Vec::new();
+    let mut item_index = 0;
 
     let mut title: String = "".to_string();
     let mut link: String = "".to_string();
@@ -74,23 +105,21 @@ fn parse_channel<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Channel,
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"title" => title = reader.read_text(b"title", &mut buf)?,
-                b"lastBuildDate" => {
-                    build_date = Some(reader.read_text(b"lastBuildDate", &mut buf)?)
-                }
-                b"link" => link = reader.read_text(b"link", &mut buf)?,
+                b"title" => title = read_text_content(reader, b"title")?,
+                b"lastBuildDate" => build_date = Some(read_text_content(reader, b"lastBuildDate")?),
+                b"link" => link = resolve_link(base, &read_text_content(reader, b"link")?),
                 b"item" => {
-                    if let Ok(item) = parse_item(reader) {
+                    let item_base = xml_base(e, reader, base)?;
+                    let (item, error) = parse_item(reader, item_base.as_ref());
+                    let keep = check_item(&item, item_index, mode, error, warnings);
+                    if keep {
                         items.push(item)
                     }
+                    item_index += 1;
                 }
                 _ => (),
             },
-            Ok(Event::End(ref e)) => {
-                if b"channel" == e.name() {
-                    break;
-                }
-            }
+            Ok(Event::End(ref e)) if b"channel" == e.name() => break,
             Ok(Event::Eof) => break,
             Err(e) => return Err(GoodMorningError::XmlParse(e)),
             _ => (),
@@ -100,9 +129,21 @@ fn parse_channel<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Channel,
         buf.clear();
     }
 
-    if title.is_empty() || link.is_empty() || items.is_empty() {
-        return Err(GoodMorningError::MissingFeedInfo);
+    let mut channel_warnings = Vec::new();
+    if title.trim().is_empty() {
+        channel_warnings.push(ParseWarning::MissingChannelTitle);
+    }
+    if link.trim().is_empty() {
+        channel_warnings.push(ParseWarning::MissingChannelLink);
+    }
+    if items.is_empty() {
+        channel_warnings.push(ParseWarning::NoItems);
+    }
+
+    if mode == ParseMode::Strict && !channel_warnings.is_empty() {
+        return Err(GoodMorningError::InvalidFeed(channel_warnings));
     }
+    warnings.append(&mut channel_warnings);
 
     Ok(Channel {
         title,
@@ -112,22 +153,73 @@ fn parse_channel<B: std::io::BufRead>(reader: &mut Reader<B>) -> Result<Channel,
     })
 }
 
-pub fn parse_rss_feed(xml: &str) -> Result<Feed, GoodMorningError> {
-    let mut reader = Reader::from_str(xml);
+// Record what's wrong with an item, returns true when the item must be kept
+fn check_item(
+    item: &Item,
+    index: usize,
+    mode: ParseMode,
+    error: Option<GoodMorningError>,
+    warnings: &mut Vec<ParseWarning>,
+) -> bool {
+    let mut missing = Vec::new();
+    if item.title.is_none() && item.description.is_none() {
+        missing.push("title or description");
+    }
+    if item.guid.is_none() && item.link.is_none() {
+        missing.push("guid or link");
+    }
+
+    for field in missing.iter() {
+        warnings.push(ParseWarning::MissingItemField {
+            index,
+            field: field.to_string(),
+        });
+    }
+
+    match error {
+        Some(error) => {
+            warnings.push(ParseWarning::InvalidItem {
+                index,
+                error: error.to_string(),
+            });
+            // a partial item is only worth keeping if we can still identify it
+            mode == ParseMode::Lenient && (item.guid.is_some() || item.link.is_some())
+        }
+        None => true,
+    }
+}
+
+/// Parse a rss document, relative links are resolved against `base` (the document url).
+/// In lenient mode an empty or incomplete channel is accepted, what's wrong is reported in `warnings`.
+pub fn parse_rss_feed<B: BufRead>(
+    input: B,
+    base: Option<&Url>,
+    mode: ParseMode,
+) -> Result<Feed, GoodMorningError> {
+    let mut reader = Reader::from_reader(input);
     reader.trim_text(true);
 
     let mut channels = Vec::new();
+    let mut warnings = Vec::new();
     let mut buf = Vec::new();
+    let mut base = base.cloned();
 
     loop {
         match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                if b"channel" == e.name() {
-                    if let Ok(channel) = parse_channel(&mut reader) {
-                        channels.push(channel)
+            Ok(Event::Start(ref e)) => match e.name() {
+                b"rss" => base = xml_base(e, &reader, base.as_ref())?,
+                b"channel" => {
+                    let channel_base = xml_base(e, &reader, base.as_ref())?;
+                    match parse_channel(&mut reader, channel_base.as_ref(), mode, &mut warnings) {
+                        Ok(channel) => channels.push(channel),
+                        Err(GoodMorningError::InvalidFeed(mut channel_warnings)) => {
+                            warnings.append(&mut channel_warnings)
+                        }
+                        Err(err) => return Err(err),
                     }
                 }
-            }
+                _ => (),
+            },
             Ok(Event::Eof) => break,
             Err(e) => return Err(GoodMorningError::XmlParse(e)),
             _ => (),
@@ -138,10 +230,13 @@ pub fn parse_rss_feed(xml: &str) -> Result<Feed, GoodMorningError> {
     }
 
     if channels.is_empty() {
-        return Err(GoodMorningError::MissingFeedInfo);
+        if warnings.is_empty() {
+            warnings.push(ParseWarning::NoChannel);
+        }
+        return Err(GoodMorningError::InvalidFeed(warnings));
     }
 
-    Ok(Feed { channels })
+    Ok(Feed { channels, warnings })
 }
 
 #[test]
@@ -178,7 +273,7 @@ pub fn should_parse_rss_sample_properly() {
         </rss>
     "#;
 
-    let feed = parse_rss_feed(rss_sample).unwrap();
+    let feed = parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict).unwrap();
     let channel = feed.channels.first().unwrap();
 
     assert_eq!(channel.title, "Liftoff News");
@@ -242,14 +337,14 @@ pub fn should_fail_on_invalid_xml() {
         </rss>
     "#;
 
-    assert!(parse_rss_feed(rss_sample).is_err());
+    assert!(parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict).is_err());
 }
 
 #[test]
 pub fn should_fail_on_empty_xml() {
     let rss_sample = r#"  "#;
 
-    assert!(parse_rss_feed(rss_sample).is_err());
+    assert!(parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict).is_err());
 }
 
 #[test]
@@ -267,7 +362,7 @@ pub fn should_fail_on_empty_items() {
         </rss>
     "#;
 
-    assert!(parse_rss_feed(rss_sample).is_err());
+    assert!(parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict).is_err());
 }
 
 #[test]
@@ -290,5 +385,208 @@ pub fn should_fail_on_missing_fields() {
         </rss>
     "#;
 
-    assert!(parse_rss_feed(rss_sample).is_err());
+    assert!(parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict).is_err());
+}
+
+#[test]
+pub fn should_resolve_relative_links() {
+    let rss_sample = r#"
+        <?xml version="1.0"?>
+        <rss version="2.0">
+            <channel>
+                <title>Liftoff News</title>
+                <link>/</link>
+                <item>
+                    <title>The Engine That Does More</title>
+                    <link>news/2003/news-VASIMR.asp</link>
+                </item>
+                <item xml:base="http://mirror.nasa.gov/archive/">
+                    <title>Astronauts' Dirty Laundry</title>
+                    <link>news-laundry.asp</link>
+                </item>
+            </channel>
+        </rss>
+    "#;
+
+    let base = Url::parse("http://liftoff.msfc.nasa.gov/rss/feed.xml").unwrap();
+    let feed = parse_rss_feed(rss_sample.as_bytes(), Some(&base), ParseMode::Strict).unwrap();
+    let channel = feed.channels.first().unwrap();
+
+    assert_eq!(channel.link, "http://liftoff.msfc.nasa.gov/");
+    assert_eq!(
+        channel.items[0].link,
+        Some("http://liftoff.msfc.nasa.gov/rss/news/2003/news-VASIMR.asp".to_string())
+    );
+    assert_eq!(
+        channel.items[1].link,
+        Some("http://mirror.nasa.gov/archive/news-laundry.asp".to_string())
+    );
+}
+
+#[test]
+pub fn should_accept_empty_channel_in_lenient_mode() {
+    let rss_sample = r#"
+        <?xml version="1.0"?>
+        <rss version="2.0">
+            <channel>
+                <title>Brand new blog</title>
+                <link>http://example.org/</link>
+            </channel>
+        </rss>
+    "#;
+
+    match parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Strict) {
+        Err(GoodMorningError::InvalidFeed(warnings)) => {
+            assert_eq!(warnings, vec![ParseWarning::NoItems])
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let feed = parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Lenient).unwrap();
+    let channel = feed.channels.first().unwrap();
+
+    assert_eq!(channel.title, "Brand new blog");
+    assert!(channel.items.is_empty());
+    assert_eq!(feed.warnings, vec![ParseWarning::NoItems]);
+}
+
+#[test]
+pub fn should_keep_partial_items_in_lenient_mode() {
+    let rss_sample = r#"
+        <?xml version="1.0"?>
+        <rss version="2.0">
+            <channel>
+                <link>http://liftoff.msfc.nasa.gov/</link>
+                <item>
+                    <description><![CDATA[A <b>title-less</b> item]]></description>
+                    <guid>http://liftoff.msfc.nasa.gov/2003/05/27.html#item571</guid>
+                </item>
+                <item>
+                    <title>Astronauts' Dirty Laundry</title>
+                </item>
+            </channel>
+        </rss>
+    "#;
+
+    let feed = parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Lenient).unwrap();
+    let channel = feed.channels.first().unwrap();
+
+    assert_eq!(channel.items.len(), 2);
+    assert_eq!(channel.items[0].title, None);
+    assert_eq!(
+        channel.items[0].description,
+        Some("A <b>title-less</b> item".to_string())
+    );
+    assert_eq!(
+        channel.items[1].title,
+        Some("Astronauts' Dirty Laundry".to_string())
+    );
+    assert_eq!(
+        feed.warnings,
+        vec![
+            ParseWarning::MissingItemField {
+                index: 1,
+                field: "guid or link".to_string()
+            },
+            ParseWarning::MissingChannelTitle,
+        ]
+    );
+}
+
+#[test]
+pub fn should_read_the_whole_of_invalid_items_in_lenient_mode() {
+    let rss_sample = r#"
+        <?xml version="1.0"?>
+        <rss version="2.0">
+            <channel>
+                <title>Liftoff News</title>
+                <link>http://liftoff.msfc.nasa.gov/</link>
+                <item>
+                    <title>Star&nbsp;City</title>
+                    <link>http://liftoff.msfc.nasa.gov/news/2003/news-starcity.asp</link>
+                    <pubDate>Tue, 03 Jun 2003 09:39:21 GMT</pubDate>
+                    <guid>http://liftoff.msfc.nasa.gov/2003/06/03.html#item573</guid>
+                </item>
+            </channel>
+        </rss>
+    "#;
+
+    let feed = parse_rss_feed(rss_sample.as_bytes(), None, ParseMode::Lenient).unwrap();
+    let channel = feed.channels.first().unwrap();
+
+    assert_eq!(channel.title, "Liftoff News");
+    assert_eq!(channel.link, "http://liftoff.msfc.nasa.gov/");
+    assert_eq!(channel.items.len(), 1);
+    assert_eq!(channel.items[0].title, None);
+    assert_eq!(
+        channel.items[0].link,
+        Some("http://liftoff.msfc.nasa.gov/news/2003/news-starcity.asp".to_string())
+    );
+    assert_eq!(
+        channel.items[0].pub_date,
+        Some("Tue, 03 Jun 2003 09:39:21 GMT".to_string())
+    );
+    assert_eq!(
+        channel.items[0].guid,
+        Some("http://liftoff.msfc.nasa.gov/2003/06/03.html#item573".to_string())
+    );
+    assert!(feed
+        .warnings
+        .iter()
+        .any(|warning| matches!(warning, ParseWarning::InvalidItem { index: 0, .. })));
+}
+
+#[test]
+pub fn should_parse_podcast_enclosures() {
+    use crate::common::Enclosure;
+
+    let rss_sample = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"
+            xmlns:media="http://search.yahoo.com/mrss/">
+            <channel>
+                <title>Hiking Treks</title>
+                <link>https://www.apple.com/itunes/podcasts/</link>
+                <item>
+                    <title>Hiking Treks Trailer</title>
+                    <enclosure length="498537" type="audio/mpeg"
+                        url="/podcasts/everest/Episode1.mp3"/>
+                    <guid>D03EEC9B-B1B4-475B-92C8-54F853FA2A22</guid>
+                    <pubDate>Tue, 8 Jan 2019 01:15:00 GMT</pubDate>
+                    <itunes:duration>1:02:03</itunes:duration>
+                    <itunes:image href="https://example.com/everest.jpg"/>
+                    <media:content url="https://example.com/everest.mp4" type="video/mp4"
+                        fileSize="1234" duration="60"/>
+                    <media:thumbnail url="https://example.com/thumbnail.jpg"/>
+                </item>
+            </channel>
+        </rss>
+    "#;
+
+    let base = Url::parse("https://example.com/feed.xml").unwrap();
+    let feed = parse_rss_feed(rss_sample.as_bytes(), Some(&base), ParseMode::Strict).unwrap();
+    let item = &feed.channels[0].items[0];
+
+    assert_eq!(item.media.duration, Some(3723));
+    assert_eq!(
+        item.media.thumbnail,
+        Some("https://example.com/everest.jpg".to_string())
+    );
+    assert_eq!(
+        item.media.enclosures,
+        vec![
+            Enclosure {
+                url: "https://example.com/podcasts/everest/Episode1.mp3".to_string(),
+                mime_type: Some("audio/mpeg".to_string()),
+                length: Some(498_537),
+                duration: None,
+            },
+            Enclosure {
+                url: "https://example.com/everest.mp4".to_string(),
+                mime_type: Some("video/mp4".to_string()),
+                length: Some(1234),
+                duration: Some(60),
+            }
+        ]
+    );
 }
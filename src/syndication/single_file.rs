@@ -0,0 +1,132 @@
+use url::Url;
+
+use super::html::parse_attributes;
+use super::link::resolve_link;
+use super::text::escape_html;
+
+/// Turn a page into a single html file readable offline: the images are replaced by what
+/// `inline` gives for their absolute url (a `data:` url), and a `<base>` keeps the other
+/// relative links pointing to the live site. Images `inline` can't load keep their live url.
+pub fn inline_images<F>(html: &str, base: &Url, mut inline: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let lower = html.to_ascii_lowercase();
+    let mut page = String::with_capacity(html.len());
+    let mut position = 0;
+
+    while let Some(start) = find_tag(&lower, position, "img") {
+        page.push_str(&html[position..start]);
+
+        let attributes_start = start + "<img".len();
+        let (attributes, len) = parse_attributes(&html[attributes_start..]);
+        position = attributes_start + len;
+        if html[position..].starts_with('>') {
+            position += 1;
+        }
+
+        // lazy loaded images only have a placeholder in `src`
+        let source = attributes
+            .iter()
+            .find(|(name, _)| name == "data-src")
+            .or_else(|| attributes.iter().find(|(name, _)| name == "src"))
+            .map(|(_, value)| resolve_link(Some(base), value));
+        let source = source.map(|url| inline(&url).unwrap_or(url));
+
+        page.push_str("<img");
+        if let Some(ref source) = source {
+            write_attribute(&mut page, "src", source);
+        }
+        for (name, value) in attributes.iter() {
+            // they would load the live images
+            if !matches!(name.as_str(), "src" | "data-src" | "srcset" | "loading") {
+                write_attribute(&mut page, name, value);
+            }
+        }
+        page.push('>');
+    }
+
+    page.push_str(&html[position..]);
+    add_base(page, base)
+}
+
+fn write_attribute(page: &mut String, name: &str, value: &str) {
+    page.push(' ');
+    page.push_str(name);
+    page.push_str("=\"");
+    page.push_str(&escape_html(value));
+    page.push('"');
+}
+
+// the pages having their own base are left as is
+fn add_base(page: String, base: &Url) -> String {
+    let lower = page.to_ascii_lowercase();
+    if find_tag(&lower, 0, "base").is_some() {
+        return page;
+    }
+
+    let tag = format!("<base href=\"{}\">", escape_html(base.as_str()));
+    match find_tag(&lower, 0, "head")
+        .and_then(|head| lower[head..].find('>').map(|end| head + end + 1))
+    {
+        Some(end) => format!("{}{}{}", &page[..end], tag, &page[end..]),
+        None => format!("{}{}", tag, page),
+    }
+}
+
+// position of the next start tag `name` in the lower-cased html
+fn find_tag(lower: &str, from: usize, name: &str) -> Option<usize> {
+    let open = format!("<{}", name);
+    let mut from = from;
+
+    while let Some(found) = lower[from..].find(&open) {
+        let start = from + found;
+        let after = start + open.len();
+        if !lower[after..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-') {
+            return Some(start);
+        }
+        from = after;
+    }
+
+    None
+}
+
+#[test]
+pub fn should_inline_images() {
+    let html = r#"<html><head><title>Pictures</title></head><body>
+        <IMG SRC="/img/a.png" alt="A &amp; B" srcset="/img/a-2x.png 2x"><imgur>not an image</imgur>
+        <img loading="lazy" src="data:," data-src="b.jpg"/>
+        <img src="https://cdn.example.org/c.gif">
+        </body></html>"#;
+    let base = Url::parse("https://example.org/blog/post.html").unwrap();
+
+    let mut requested = Vec::new();
+    let page = inline_images(html, &base, |url| {
+        requested.push(url.to_string());
+        if url.ends_with(".gif") {
+            None
+        } else {
+            Some(format!("data:image/png;base64,{}", url.len()))
+        }
+    });
+
+    assert_eq!(
+        requested,
+        vec![
+            "https://example.org/img/a.png",
+            "https://example.org/blog/b.jpg",
+            "https://cdn.example.org/c.gif"
+        ]
+    );
+    assert_eq!(
+        page,
+        r#"<html><head><base href="https://example.org/blog/post.html"><title>Pictures</title></head><body>
+        <img src="data:image/png;base64,29" alt="A &amp; B"><imgur>not an image</imgur>
+        <img src="data:image/png;base64,30">
+        <img src="https://cdn.example.org/c.gif">
+        </body></html>"#
+    );
+
+    let page = r#"<head><base href="/"></head>"#;
+    assert_eq!(inline_images(page, &base, |_| None), page);
+}
@@ -0,0 +1,399 @@
+use std::str::from_utf8;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::common::GoodMorningError;
+
+// Elements without end tag in html
+const VOID_ELEMENTS: [&[u8]; 9] = [
+    b"br", b"hr", b"img", b"input", b"meta", b"link", b"area", b"col", b"wbr",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextType {
+    Text,
+    Html,
+    Xhtml,
+}
+
+/// Atom text construct (title, summary, content...)
+/// `value` holds plain text for `Text` and markup for `Html` and `Xhtml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextConstruct {
+    pub kind: TextType,
+    pub value: String,
+}
+
+impl TextConstruct {
+    pub fn text(&self) -> String {
+        match self.kind {
+            TextType::Text => self.value.clone(),
+            TextType::Html | TextType::Xhtml => html_to_text(&self.value),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.trim().is_empty()
+    }
+}
+
+impl Default for TextConstruct {
+    fn default() -> TextConstruct {
+        TextConstruct {
+            kind: TextType::Text,
+            value: "".to_string(),
+        }
+    }
+}
+
+/// Read a text construct, the reader must be just after its start element.
+/// Returns `None` for out of line (`src`) or non textual content.
+pub fn parse_text_construct<B: std::io::BufRead>(
+    event: &BytesStart,
+    reader: &mut Reader<B>,
+) -> Result<Option<TextConstruct>, GoodMorningError> {
+    let end = event.name().to_vec();
+    let mut kind = TextType::Text;
+    let mut inline = true;
+
+    for attr in event.attributes() {
+        let attr = attr?;
+        match attr.key {
+            b"type" => {
+                kind = match attr.unescape_and_decode_value(reader)?.trim() {
+                    "" | "text" | "text/plain" => TextType::Text,
+                    "html" | "text/html" => TextType::Html,
+                    "xhtml" | "application/xhtml+xml" => TextType::Xhtml,
+                    _ => {
+                        inline = false;
+                        TextType::Text
+                    }
+                }
+            }
+            b"src" => inline = false,
+            _ => (),
+        }
+    }
+
+    if !inline {
+        reader.read_to_end(&end, &mut Vec::new())?;
+        return Ok(None);
+    }
+
+    let value = match kind {
+        TextType::Text | TextType::Html => read_text_content(reader, &end)?,
+        TextType::Xhtml => read_xhtml_content(reader, &end)?,
+    };
+
+    Ok(Some(TextConstruct { kind, value }))
+}
+
+/// Read text and CDATA up to the `end` element, nested elements are ignored.
+pub fn read_text_content<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+    end: &[u8],
+) -> Result<String, GoodMorningError> {
+    let mut buf = Vec::new();
+    let mut text = String::new();
+    let mut depth = 0;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Text(ref e)) => text.push_str(&e.unescape_and_decode(reader)?),
+            Ok(Event::CData(ref e)) => {
+                text.push_str(from_utf8(e).map_err(|_| GoodMorningError::Parse)?)
+            }
+            Ok(Event::Start(ref e)) if e.name() == end => depth += 1,
+            Ok(Event::End(ref e)) if e.name() == end => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            Ok(Event::Eof) => return Err(GoodMorningError::Parse),
+            Err(e) => return Err(GoodMorningError::XmlParse(e)),
+            _ => (),
+        }
+
+        buf.clear();
+    }
+
+    Ok(text)
+}
+
+// Serialize the children of the xhtml wrapper div back to markup
+fn read_xhtml_content<B: std::io::BufRead>(
+    reader: &mut Reader<B>,
+    end: &[u8],
+) -> Result<String, GoodMorningError> {
+    let mut buf = Vec::new();
+    let mut html = String::new();
+    let mut depth = 0;
+
+    // whitespaces between inline elements matter, the feed parsers trim text otherwise
+    reader.trim_text(false);
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                depth += 1;
+                // skip the wrapper div
+                if depth > 1 {
+                    let raw = from_utf8(e).map_err(|_| GoodMorningError::Parse)?;
+                    let name = local_name(e.name());
+                    html.push('<');
+                    html.push_str(from_utf8(name).map_err(|_| GoodMorningError::Parse)?);
+                    html.push_str(&raw[e.name().len()..]);
+                    html.push('>');
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if depth == 0 {
+                    if e.name() == end {
+                        break;
+                    }
+                    return Err(GoodMorningError::Parse);
+                }
+                if depth > 1 && !VOID_ELEMENTS.contains(&local_name(e.name())) {
+                    html.push_str("</");
+                    html.push_str(
+                        from_utf8(local_name(e.name())).map_err(|_| GoodMorningError::Parse)?,
+                    );
+                    html.push('>');
+                }
+                depth -= 1;
+            }
+            Ok(Event::Text(ref e)) if depth > 0 => {
+                html.push_str(from_utf8(e.escaped()).map_err(|_| GoodMorningError::Parse)?)
+            }
+            Ok(Event::CData(ref e)) => html.push_str(&escape_html(
+                from_utf8(e).map_err(|_| GoodMorningError::Parse)?,
+            )),
+            Ok(Event::Eof) => return Err(GoodMorningError::Parse),
+            Err(e) => return Err(GoodMorningError::XmlParse(e)),
+            _ => (),
+        }
+
+        buf.clear();
+    }
+
+    reader.trim_text(true);
+
+    Ok(html.trim().to_string())
+}
+
+fn local_name(name: &[u8]) -> &[u8] {
+    match name.iter().position(|&c| c == b':') {
+        Some(i) => &name[i + 1..],
+        None => name,
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Plain text view of an html fragment: tags are dropped, entities decoded and whitespaces collapsed.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+
+        // block elements separate words
+        let tag = rest[1..end].trim_start_matches('/').to_ascii_lowercase();
+        if !is_inline_tag(&tag) {
+            text.push(' ');
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    if !rest.contains('>') {
+        text.push_str(rest);
+    }
+
+    decode_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_inline_tag(tag: &str) -> bool {
+    let name = tag
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or("");
+
+    matches!(
+        name,
+        "a" | "abbr"
+            | "b"
+            | "cite"
+            | "code"
+            | "em"
+            | "i"
+            | "mark"
+            | "q"
+            | "s"
+            | "small"
+            | "span"
+            | "strong"
+            | "sub"
+            | "sup"
+            | "u"
+    )
+}
+
+/// Decode html character references, unknown ones are kept as is.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..=end]).map(|c| (c, end + 2)));
+
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(code) = entity.strip_prefix('#') {
+        let code = match code.strip_prefix(|c| c == 'x' || c == 'X') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse().ok(),
+        };
+        return code.and_then(std::char::from_u32);
+    }
+
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "ecirc" => 'ê',
+        "agrave" => 'à',
+        "ccedil" => 'ç',
+        "ouml" => 'ö',
+        "uuml" => 'ü',
+        "auml" => 'ä',
+        "szlig" => 'ß',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+#[test]
+pub fn should_convert_html_to_text() {
+    assert_eq!(
+        html_to_text(
+            "<p>Robots <em>run</em> amok&nbsp;&amp; fast</p><p>Second&#x20;paragraph &#8230;</p>"
+        ),
+        "Robots run amok & fast Second paragraph …"
+    );
+    assert_eq!(html_to_text("AT&T <3 Q&A"), "AT&T <3 Q&A");
+    assert_eq!(html_to_text("line<br/>break"), "line break");
+}
+
+#[test]
+pub fn should_parse_text_constructs() {
+    let xml = r#"<entry>
+        <title>Less &lt;em&gt; than &amp; more</title>
+        <title type="html">Robots &lt;em&gt;Run&lt;/em&gt; Amok &amp;amp; more</title>
+        <title type="html"><![CDATA[Robots <em>Run</em> Amok]]></title>
+        <title type="xhtml">
+            <div xmlns="http://www.w3.org/1999/xhtml">Robots <xhtml:em>Run</xhtml:em><br/> Amok &amp; more</div>
+        </title>
+        <content type="video/mp4" src="http://example.org/movie.mp4"/>
+    </entry>"#;
+
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true).expand_empty_elements(true);
+    let mut buf = Vec::new();
+    let mut constructs = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Event::Start(ref e) if e.name() != b"entry" => {
+                constructs.push(parse_text_construct(e, &mut reader).unwrap())
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    let text = constructs[0].as_ref().unwrap();
+    assert_eq!(text.kind, TextType::Text);
+    assert_eq!(text.text(), "Less <em> than & more");
+
+    let html = constructs[1].as_ref().unwrap();
+    assert_eq!(html.kind, TextType::Html);
+    assert_eq!(html.value, "Robots <em>Run</em> Amok &amp; more");
+    assert_eq!(html.text(), "Robots Run Amok & more");
+
+    let cdata = constructs[2].as_ref().unwrap();
+    assert_eq!(cdata.value, "Robots <em>Run</em> Amok");
+
+    let xhtml = constructs[3].as_ref().unwrap();
+    assert_eq!(xhtml.kind, TextType::Xhtml);
+    assert_eq!(xhtml.value, "Robots <em>Run</em><br> Amok &amp; more");
+    assert_eq!(xhtml.text(), "Robots Run Amok & more");
+
+    assert_eq!(constructs[4], None);
+}